@@ -0,0 +1,78 @@
+//! Measure the memory held by the read buffers of 250 connections, each
+//! having read one 1MB request, then again once each has read a few small
+//! requests.
+//!
+//! Run with `cargo bench --bench buffers`.
+
+use mini_redis::{Connection, Frame};
+
+use bytes::Bytes;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::io::{AsyncWriteExt, DuplexStream};
+
+const CONNECTIONS: usize = 250;
+const LARGE_REQUEST: usize = 1024 * 1024;
+const SMALL_REQUESTS: usize = 10;
+
+/// Counts the bytes currently allocated.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let mut pairs: Vec<(DuplexStream, Connection<DuplexStream>)> = (0..CONNECTIONS)
+        .map(|_| {
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            (client, Connection::new(server))
+        })
+        .collect();
+    let baseline = ALLOCATED.load(Ordering::Relaxed);
+
+    let large = Bytes::from(vec![b'x'; LARGE_REQUEST]);
+    let mut encoded = format!("${}\r\n", LARGE_REQUEST).into_bytes();
+    encoded.extend_from_slice(&large);
+    encoded.extend_from_slice(b"\r\n");
+    let encoded = Bytes::from(encoded);
+
+    for (client, conn) in &mut pairs {
+        let (_, frame) = tokio::join!(client.write_all(&encoded), conn.read_frame());
+        assert_eq!(Some(Frame::Bulk(large.clone())), frame.unwrap());
+    }
+    report("after a large request", baseline);
+
+    for _ in 0..SMALL_REQUESTS {
+        for (client, conn) in &mut pairs {
+            client.write_all(b"+PING\r\n").await.unwrap();
+            conn.read_frame().await.unwrap().unwrap();
+        }
+    }
+    report("after small requests", baseline);
+}
+
+fn report(when: &str, baseline: usize) {
+    let held = ALLOCATED.load(Ordering::Relaxed).saturating_sub(baseline);
+    println!(
+        "{} connections {}: {}KB held, {}KB per connection",
+        CONNECTIONS,
+        when,
+        held / 1024,
+        held / CONNECTIONS / 1024
+    );
+}
@@ -0,0 +1,61 @@
+//! Measure a request/reply round trip through two `Connection`s joined by an
+//! in-memory pipe, for small and 64KB replies. No socket is involved, so this
+//! is the cost of buffering, parsing and encoding alone.
+//!
+//! Run with `cargo bench --bench connection`.
+
+use mini_redis::{Connection, Frame};
+
+use bytes::Bytes;
+use std::time::{Duration, Instant};
+
+const ROUND_TRIPS: usize = 100_000;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    for (name, reply) in [
+        ("small reply", Frame::Simple("OK".to_string())),
+        (
+            "64KB reply",
+            Frame::Bulk(Bytes::from(vec![b'x'; 64 * 1024])),
+        ),
+    ] {
+        let elapsed = run(reply).await;
+
+        println!(
+            "{}: {} round trips in {:?}, {:?} per round trip",
+            name,
+            ROUND_TRIPS,
+            elapsed,
+            elapsed / ROUND_TRIPS as u32
+        );
+    }
+}
+
+async fn run(reply: Frame) -> Duration {
+    let (client, server) = tokio::io::duplex(64 * 1024);
+    let mut client = Connection::new(client);
+    let mut server = Connection::new(server);
+
+    let request = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"GET")),
+        Frame::Bulk(Bytes::from_static(b"key:1")),
+    ]);
+
+    let server = tokio::spawn(async move {
+        while let Some(_request) = server.read_frame().await.unwrap() {
+            server.write_frame(&reply).await.unwrap();
+        }
+    });
+
+    let start = Instant::now();
+    for _ in 0..ROUND_TRIPS {
+        client.write_frame(&request).await.unwrap();
+        client.read_frame().await.unwrap().unwrap();
+    }
+    let elapsed = start.elapsed();
+
+    drop(client);
+    server.await.unwrap();
+    elapsed
+}
@@ -0,0 +1,69 @@
+//! Measure `Db::set` and `Db::get` from a single thread, then from several
+//! threads contending for the state lock.
+//!
+//! Run with `cargo bench --bench db`.
+
+use mini_redis::db::Db;
+
+use bytes::Bytes;
+use std::hint::black_box;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const KEYS: usize = 10_000;
+const OPERATIONS_PER_THREAD: usize = 1_000_000;
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    // `Db::new` spawns the expiration task.
+    let db = rt.block_on(async { Db::new() });
+
+    let keys: Vec<String> = (0..KEYS).map(|i| format!("key:{}", i)).collect();
+    for key in &keys {
+        db.set(key.clone(), Bytes::from_static(b"value"), None)
+            .unwrap();
+    }
+
+    for threads in [1, 2, 4, 8] {
+        let elapsed = run(&db, &keys, threads);
+        let operations = (threads * OPERATIONS_PER_THREAD) as u32;
+
+        println!(
+            "{} threads: {} operations in {:?}, {:?} per operation",
+            threads,
+            operations,
+            elapsed,
+            elapsed / operations
+        );
+    }
+
+    db.shutdown_clean_task();
+}
+
+/// Each thread does three gets for every set, on keys spread over the whole
+/// key space.
+fn run(db: &Db, keys: &[String], threads: usize) -> Duration {
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        for t in 0..threads {
+            scope.spawn(move || {
+                for n in 0..OPERATIONS_PER_THREAD {
+                    let key = &keys[(n * 7 + t) % keys.len()];
+                    if n % 4 == 0 {
+                        db.set(key.clone(), Bytes::from_static(b"value"), None)
+                            .unwrap();
+                    } else {
+                        black_box(db.get(key).unwrap());
+                    }
+                }
+            });
+        }
+    });
+
+    start.elapsed()
+}
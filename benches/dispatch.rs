@@ -0,0 +1,58 @@
+//! Measure `Command::from_frame` on a mix of commands resembling a cache
+//! workload: mostly reads, some writes, a few other commands.
+//!
+//! Run with `cargo bench --bench dispatch`.
+
+use mini_redis::cmd::Command;
+use mini_redis::Frame;
+
+use bytes::Bytes;
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: usize = 1_000_000;
+
+fn main() {
+    let mix = [
+        (60, command(&["GET", "key:1"])),
+        (25, command(&["SET", "key:1", "value"])),
+        (5, command(&["SET", "key:2", "value", "PX", "1000"])),
+        (4, command(&["PUBLISH", "channel", "message"])),
+        (3, command(&["PING"])),
+        (2, command(&["INFO", "replication"])),
+        (1, command(&["NOSUCHCOMMAND", "arg"])),
+    ];
+
+    let frames: Vec<Frame> = mix
+        .iter()
+        .flat_map(|(weight, frame)| std::iter::repeat_n(frame.clone(), *weight))
+        .collect();
+
+    // Warm up, building any lazily initialized state.
+    for frame in &frames {
+        black_box(Command::from_frame(frame.clone()).unwrap());
+    }
+
+    let inputs: Vec<Frame> = frames.iter().cycle().take(ITERATIONS).cloned().collect();
+
+    let start = Instant::now();
+    for frame in inputs {
+        black_box(Command::from_frame(black_box(frame)).unwrap());
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "dispatched {} commands in {:?}, {:?} per command",
+        ITERATIONS,
+        elapsed,
+        elapsed / ITERATIONS as u32
+    );
+}
+
+fn command(args: &[&str]) -> Frame {
+    Frame::Array(
+        args.iter()
+            .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg.as_bytes())))
+            .collect(),
+    )
+}
@@ -0,0 +1,70 @@
+//! Compare the active expiration strategies while 1M keys expire.
+//!
+//! For each strategy, the keys are given deadlines spread over 100ms, then a
+//! non-expiring key is read in a loop until the background task has removed
+//! the last key.
+//! The reported read latency shows how long the reaper holds the state lock.
+//!
+//! Run with `cargo bench --bench expiration`.
+
+use mini_redis::config::ActiveExpire;
+use mini_redis::db::Db;
+
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::time::Instant;
+
+const KEYS: usize = 1_000_000;
+
+fn main() {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    for mode in [ActiveExpire::Precise, ActiveExpire::Sampling] {
+        rt.block_on(run(mode));
+    }
+}
+
+async fn run(mode: ActiveExpire) {
+    let db = Db::new();
+    db.update_settings(|s| {
+        s.active_expire = mode;
+        Ok(())
+    })
+    .unwrap();
+
+    db.set("probe".to_string(), Bytes::from("value"), None)
+        .unwrap();
+
+    let ttl = Duration::from_millis(500);
+    let value = Bytes::from("value");
+    for i in 0..KEYS {
+        let spread = Duration::from_millis((i % 100) as u64);
+        db.set(format!("key:{}", i), value.clone(), Some(ttl + spread))
+            .unwrap();
+    }
+    let start = Instant::now();
+    let mut reads = 0u64;
+    let mut max_read = Duration::ZERO;
+
+    // Reading the keys would remove them lazily, so wait for the expiration
+    // records to run out instead.
+    while !db.expirations(1).is_empty() {
+        let read_start = Instant::now();
+        let _ = db.get("probe");
+        max_read = max_read.max(read_start.elapsed());
+        reads += 1;
+
+        tokio::task::yield_now().await;
+    }
+
+    println!(
+        "{:?}: all keys removed after {:?}, {} reads, max read latency {:?}",
+        mode,
+        start.elapsed(),
+        reads,
+        max_read
+    );
+}
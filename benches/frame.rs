@@ -0,0 +1,54 @@
+//! Measure `Frame::parse` on representative payloads: a simple string, a
+//! 1KB bulk string and a 1000-element array of bulk strings.
+//!
+//! Run with `cargo bench --bench frame`.
+
+use mini_redis::Frame;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const BYTES_PER_CASE: usize = 64 * 1024 * 1024;
+
+fn main() {
+    let mut large_array = b"*1000\r\n".to_vec();
+    for i in 0..1000 {
+        let value = format!("value:{}", i);
+        large_array.extend_from_slice(format!("${}\r\n{}\r\n", value.len(), value).as_bytes());
+    }
+
+    let mut bulk = b"$1024\r\n".to_vec();
+    bulk.extend_from_slice(&[b'x'; 1024]);
+    bulk.extend_from_slice(b"\r\n");
+
+    let cases: [(&str, &[u8]); 3] = [
+        ("simple", b"+OK\r\n"),
+        ("bulk 1KB", &bulk),
+        ("array of 1000 bulks", &large_array),
+    ];
+
+    for (name, input) in cases {
+        let iterations = (BYTES_PER_CASE / input.len()).max(1);
+        let elapsed = run(input, iterations);
+
+        println!(
+            "{}: parsed {} frames in {:?}, {:?} per frame",
+            name,
+            iterations,
+            elapsed,
+            elapsed / iterations as u32
+        );
+    }
+}
+
+fn run(input: &[u8], iterations: usize) -> Duration {
+    // Check the input once, outside of the measurement.
+    let (len, _) = Frame::parse(input).unwrap();
+    assert_eq!(input.len(), len);
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(Frame::parse(black_box(input)).unwrap());
+    }
+    start.elapsed()
+}
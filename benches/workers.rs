@@ -0,0 +1,84 @@
+//! Compare running commands on their connection's task with running them on
+//! the worker pool (`command-workers`), with many connections sending a mix
+//! of reads and writes.
+//!
+//! Run with `cargo bench --bench workers`.
+
+use mini_redis::testing::TestServer;
+use mini_redis::{Connection, Frame};
+
+use bytes::Bytes;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+
+const CONNECTIONS: usize = 100;
+const REQUESTS_PER_CONNECTION: usize = 2_000;
+
+#[tokio::main]
+async fn main() {
+    for workers in [0, 2, 4, 8] {
+        let elapsed = run(workers).await;
+        let requests = (CONNECTIONS * REQUESTS_PER_CONNECTION) as u32;
+
+        println!(
+            "command-workers {}: {} requests in {:?}, {:?} per request",
+            workers,
+            requests,
+            elapsed,
+            elapsed / requests
+        );
+    }
+}
+
+async fn run(workers: usize) -> Duration {
+    let server = TestServer::start().await.unwrap();
+
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    let workers = workers.to_string();
+    request(&mut conn, &["CONFIG", "SET", "command-workers", &workers]).await;
+
+    let mut conns = Vec::with_capacity(CONNECTIONS);
+    for _ in 0..CONNECTIONS {
+        conns.push(Connection::new(
+            TcpStream::connect(server.addr()).await.unwrap(),
+        ));
+    }
+
+    let start = Instant::now();
+
+    let tasks: Vec<_> = conns
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut conn)| {
+            tokio::spawn(async move {
+                let key = format!("key:{}", i);
+                for n in 0..REQUESTS_PER_CONNECTION {
+                    // Three reads for every write.
+                    if n % 4 == 0 {
+                        request(&mut conn, &["SET", &key, "value"]).await;
+                    } else {
+                        request(&mut conn, &["GET", &key]).await;
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let elapsed = start.elapsed();
+    server.shutdown().await;
+    elapsed
+}
+
+async fn request(conn: &mut Connection, args: &[&str]) -> Frame {
+    let frame = Frame::Array(
+        args.iter()
+            .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg.as_bytes())))
+            .collect(),
+    );
+    conn.write_frame(&frame).await.unwrap();
+    conn.read_frame().await.unwrap().unwrap()
+}
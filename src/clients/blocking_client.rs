@@ -102,6 +102,15 @@ impl BlockingClient {
         self.rt.block_on(self.inner.get(key))
     }
 
+    /// Get the value of key as a UTF-8 string.
+    ///
+    /// If the key does not exist the special value `None` is returned. A value
+    /// that is not valid UTF-8 is an error, which downcasts to
+    /// `std::string::FromUtf8Error`.
+    pub fn get_string(&mut self, key: &str) -> crate::Result<Option<String>> {
+        self.rt.block_on(self.inner.get_string(key))
+    }
+
     /// Set `key` to hold the given `value`.
     ///
     /// The `value` is associated with `key` until it is overwritten by the next
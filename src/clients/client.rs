@@ -164,6 +164,35 @@ impl Client {
         }
     }
 
+    /// Get the value of key as a UTF-8 string.
+    ///
+    /// If the key does not exist the special value `None` is returned. A value
+    /// that is not valid UTF-8 is an error, which downcasts to
+    /// `std::string::FromUtf8Error`.
+    ///
+    /// # Examples
+    ///
+    /// Demonstrates basic usage.
+    ///
+    /// ```no_run
+    /// use mini_redis::clients::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut client = Client::connect("localhost:6379").await.unwrap();
+    ///
+    ///     let val = client.get_string("foo").await.unwrap();
+    ///     println!("Got = {:?}", val);
+    /// }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn get_string(&mut self, key: &str) -> crate::Result<Option<String>> {
+        match self.get(key).await? {
+            Some(value) => Ok(Some(String::from_utf8(value.to_vec())?)),
+            None => Ok(None),
+        }
+    }
+
     /// Set `key` to hold the given `value`.
     ///
     /// The `value` is associated with `key` until it is overwritten by the next
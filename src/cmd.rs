@@ -1,35 +1,128 @@
+mod error;
+pub use error::CommandError;
+
 mod get;
 pub use get::Get;
 
+mod mget;
+pub use mget::Mget;
+
+mod getdel;
+pub use getdel::GetDel;
+
 mod publish;
 pub use publish::Publish;
 
 mod set;
 pub use set::Set;
 
+mod setnx;
+pub use setnx::SetNx;
+
+mod mset;
+pub use mset::Mset;
+
+mod append;
+pub use append::Append;
+
 mod subscribe;
 pub use subscribe::{Subscribe, Unsubscribe};
 
 mod ping;
 pub use ping::Ping;
 
+mod hello;
+pub use hello::Hello;
+
+mod info;
+pub use info::Info;
+
+mod replicaof;
+pub use replicaof::ReplicaOf;
+
+mod lolwut;
+pub use lolwut::Lolwut;
+
+mod config;
+pub use config::Config;
+
+mod hotkeys;
+pub use hotkeys::Hotkeys;
+
+mod expire;
+pub use expire::Expire;
+
+mod expiretime;
+pub use expiretime::ExpireTime;
+
+mod exists;
+pub use exists::Exists;
+
+mod incr;
+pub use incr::Incr;
+
+mod persist;
+pub use persist::Persist;
+
+mod debug;
+pub use debug::Debug;
+
+mod cluster;
+pub use cluster::Cluster;
+
+mod xadd;
+pub use xadd::XAdd;
+
+mod xlen;
+pub use xlen::XLen;
+
+mod xrange;
+pub use xrange::XRange;
+
+mod xread;
+pub use xread::XRead;
+
 mod unknown;
 pub use unknown::Unknown;
 
 use crate::{connection::Connection, db::Db, shutdown::Shutdown};
 
 use super::frame::Frame;
+use std::collections::HashMap;
+use std::sync::OnceLock;
 use std::vec::IntoIter;
 
 use bytes::Bytes;
 
 pub enum Command {
     Get(Get),
+    Mget(Mget),
+    GetDel(GetDel),
     Publish(Publish),
     Set(Set),
+    SetNx(SetNx),
+    Mset(Mset),
+    Append(Append),
     Subscribe(Subscribe),
     Unsubscribe(Unsubscribe),
     Ping(Ping),
+    Hello(Hello),
+    Info(Info),
+    ReplicaOf(ReplicaOf),
+    Lolwut(Lolwut),
+    Config(Config),
+    Hotkeys(Hotkeys),
+    Expire(Expire),
+    ExpireTime(ExpireTime),
+    Exists(Exists),
+    Incr(Incr),
+    Persist(Persist),
+    Debug(Debug),
+    Cluster(Cluster),
+    XAdd(XAdd),
+    XLen(XLen),
+    XRange(XRange),
+    XRead(XRead),
     Unknown(Unknown),
 }
 
@@ -37,23 +130,36 @@ impl Command {
     pub fn from_frame(frame: Frame) -> crate::Result<Command> {
         let mut parse = Parse::new(frame)?;
         let cmd = match parse.next_string()? {
-            Some(name) => match name.as_str() {
-                "get" => Command::Get(Get::from_frame(parse)?),
-                "publish" => Command::Publish(Publish::from_frame(parse)?),
-                "set" => Command::Set(Set::from_frame(parse)?),
-                "subscribe" => Command::Subscribe(Subscribe::from_frame(parse)?),
-                "unsubscribe" => Command::Unsubscribe(Unsubscribe::from_frame(parse)?),
-                "ping" => Command::Ping(Ping::from_frame(parse)?),
-                _ => Command::Unknown(Unknown::new(name)),
+            Some(name) => match parsers().get(name.to_lowercase().as_str()) {
+                Some(parser) => parser(parse)?,
+                None => Command::Unknown(Unknown::from_frame(name, parse)),
             },
             None => {
-                return Err(format!("protocol error; expected command name").into());
+                return Err("protocol error; expected command name".into());
             }
         };
 
         Ok(cmd)
     }
 
+    /// Parse a command like `from_frame`, but hand back a `CommandError`
+    /// raised by the command's parser, such as a syntax error in its options,
+    /// so it can be replied to. Any other error closes the connection.
+    pub(crate) fn from_frame_or_reply(
+        frame: Frame,
+    ) -> crate::Result<Result<Command, CommandError>> {
+        // An empty array holds no command name. Reply with an error but keep
+        // the connection open, as for an unknown command.
+        if matches!(&frame, Frame::Array(args) if args.is_empty()) {
+            return Ok(Err(CommandError::Err("empty command".to_string())));
+        }
+
+        match Command::from_frame(frame) {
+            Ok(cmd) => Ok(Ok(cmd)),
+            Err(err) => err.downcast::<CommandError>().map(|err| Err(*err)),
+        }
+    }
+
     pub async fn apply(
         self,
         db: &mut Db,
@@ -64,10 +170,32 @@ impl Command {
 
         match self {
             Get(cmd) => cmd.apply(db, conn).await,
+            Mget(cmd) => cmd.apply(db, conn).await,
+            GetDel(cmd) => cmd.apply(db, conn).await,
             Publish(cmd) => cmd.apply(db, conn).await,
             Set(cmd) => cmd.apply(db, conn).await,
+            SetNx(cmd) => cmd.apply(db, conn).await,
+            Mset(cmd) => cmd.apply(db, conn).await,
+            Append(cmd) => cmd.apply(db, conn).await,
             Subscribe(cmd) => cmd.apply(db, conn, shutdown).await,
             Ping(cmd) => cmd.apply(conn).await,
+            Hello(cmd) => cmd.apply(db, conn).await,
+            Info(cmd) => cmd.apply(db, conn).await,
+            ReplicaOf(cmd) => cmd.apply(db, conn).await,
+            Lolwut(cmd) => cmd.apply(conn).await,
+            Config(cmd) => cmd.apply(db, conn).await,
+            Hotkeys(cmd) => cmd.apply(db, conn).await,
+            Expire(cmd) => cmd.apply(db, conn).await,
+            ExpireTime(cmd) => cmd.apply(db, conn).await,
+            Exists(cmd) => cmd.apply(db, conn).await,
+            Incr(cmd) => cmd.apply(db, conn).await,
+            Persist(cmd) => cmd.apply(db, conn).await,
+            Debug(cmd) => cmd.apply(db, conn).await,
+            Cluster(cmd) => cmd.apply(db, conn).await,
+            XAdd(cmd) => cmd.apply(db, conn).await,
+            XLen(cmd) => cmd.apply(db, conn).await,
+            XRange(cmd) => cmd.apply(db, conn).await,
+            XRead(cmd) => cmd.apply(db, conn, shutdown).await,
             Unknown(cmd) => cmd.apply(conn).await,
             // `Unsubscribe` cannot be applied. It may only be received from the
             // context of a `Subscribe` command.
@@ -80,16 +208,234 @@ impl Command {
 
         match self {
             Get(_) => "get",
-            Publish(_) => "pub",
+            Mget(_) => "mget",
+            GetDel(_) => "getdel",
+            Publish(_) => "publish",
             Set(_) => "set",
+            SetNx(_) => "setnx",
+            Mset(_) => "mset",
+            Append(_) => "append",
             Subscribe(_) => "subscribe",
             Unsubscribe(_) => "unsubscribe",
             Ping(_) => "ping",
+            Hello(_) => "hello",
+            Info(_) => "info",
+            ReplicaOf(_) => "replicaof",
+            Lolwut(_) => "lolwut",
+            Config(_) => "config",
+            Hotkeys(_) => "hotkeys",
+            Expire(cmd) => cmd.get_name(),
+            ExpireTime(cmd) => cmd.get_name(),
+            Exists(_) => "exists",
+            Incr(cmd) => cmd.get_name(),
+            Persist(_) => "persist",
+            Debug(_) => "debug",
+            Cluster(_) => "cluster",
+            XAdd(_) => "xadd",
+            XLen(_) => "xlen",
+            XRange(_) => "xrange",
+            XRead(_) => "xread",
             Unknown(cmd) => cmd.get_name(),
         }
     }
 }
 
+/// Properties of a command, as in the flags reported by Redis `COMMAND INFO`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flags(u8);
+
+impl Flags {
+    pub const NONE: Flags = Flags(0);
+    /// The command is part of pub/sub.
+    pub const PUBSUB: Flags = Flags(1);
+    /// The command may run while the connection is subscribed to channels.
+    pub const ALLOWED_IN_SUBSCRIBE: Flags = Flags(1 << 1);
+
+    pub const fn union(self, other: Flags) -> Flags {
+        Flags(self.0 | other.0)
+    }
+
+    /// Returns whether every flag of `other` is set.
+    pub const fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl Command {
+    pub fn flags(&self) -> Flags {
+        use Command::*;
+
+        match self {
+            Subscribe(_) | Unsubscribe(_) => Flags::PUBSUB.union(Flags::ALLOWED_IN_SUBSCRIBE),
+            Publish(_) => Flags::PUBSUB,
+            Ping(_) => Flags::ALLOWED_IN_SUBSCRIBE,
+            _ => Flags::NONE,
+        }
+    }
+}
+
+/// A command that only needs the database to run, and so may be run away from
+/// its connection by the worker pool.
+pub(crate) enum DbCommand {
+    Get(Get),
+    Mget(Mget),
+    GetDel(GetDel),
+    Exists(Exists),
+    Expire(Expire),
+    Incr(Incr),
+    Persist(Persist),
+    Publish(Publish),
+    Set(Set),
+    SetNx(SetNx),
+    Mset(Mset),
+    Append(Append),
+    XAdd(XAdd),
+    XLen(XLen),
+    XRange(XRange),
+}
+
+impl Command {
+    /// Returns the command as a `DbCommand`, or gives it back if it needs its
+    /// connection.
+    pub(crate) fn into_db_command(self) -> Result<DbCommand, Command> {
+        match self {
+            Command::Get(cmd) => Ok(DbCommand::Get(cmd)),
+            Command::Mget(cmd) => Ok(DbCommand::Mget(cmd)),
+            Command::GetDel(cmd) => Ok(DbCommand::GetDel(cmd)),
+            Command::Exists(cmd) => Ok(DbCommand::Exists(cmd)),
+            Command::Expire(cmd) => Ok(DbCommand::Expire(cmd)),
+            Command::Incr(cmd) => Ok(DbCommand::Incr(cmd)),
+            Command::Persist(cmd) => Ok(DbCommand::Persist(cmd)),
+            Command::Publish(cmd) => Ok(DbCommand::Publish(cmd)),
+            Command::Set(cmd) => Ok(DbCommand::Set(cmd)),
+            Command::SetNx(cmd) => Ok(DbCommand::SetNx(cmd)),
+            Command::Mset(cmd) => Ok(DbCommand::Mset(cmd)),
+            Command::Append(cmd) => Ok(DbCommand::Append(cmd)),
+            Command::XAdd(cmd) => Ok(DbCommand::XAdd(cmd)),
+            Command::XLen(cmd) => Ok(DbCommand::XLen(cmd)),
+            Command::XRange(cmd) => Ok(DbCommand::XRange(cmd)),
+            cmd => Err(cmd),
+        }
+    }
+}
+
+impl DbCommand {
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        match self {
+            DbCommand::Get(cmd) => cmd.execute(db),
+            DbCommand::Mget(cmd) => cmd.execute(db),
+            DbCommand::GetDel(cmd) => cmd.execute(db),
+            DbCommand::Exists(cmd) => cmd.execute(db),
+            DbCommand::Expire(cmd) => cmd.execute(db),
+            DbCommand::Incr(cmd) => cmd.execute(db),
+            DbCommand::Persist(cmd) => cmd.execute(db),
+            DbCommand::Publish(cmd) => cmd.execute(db),
+            DbCommand::Set(cmd) => cmd.execute(db),
+            DbCommand::SetNx(cmd) => cmd.execute(db),
+            DbCommand::Mset(cmd) => cmd.execute(db),
+            DbCommand::Append(cmd) => cmd.execute(db),
+            DbCommand::XAdd(cmd) => cmd.execute(db),
+            DbCommand::XLen(cmd) => cmd.execute(db),
+            DbCommand::XRange(cmd) => cmd.execute(db),
+        }
+    }
+}
+
+/// Parses the arguments of a command into a `Command`.
+type Parser = fn(Parse) -> crate::Result<Command>;
+
+/// Returns the parser of every known command, keyed by lowercase name. The
+/// table is built on first use.
+fn parsers() -> &'static HashMap<&'static str, Parser> {
+    static PARSERS: OnceLock<HashMap<&'static str, Parser>> = OnceLock::new();
+
+    PARSERS.get_or_init(|| {
+        let mut parsers: HashMap<&'static str, Parser> = HashMap::new();
+        parsers.insert("get", |parse| Ok(Command::Get(Get::from_frame(parse)?)));
+        parsers.insert("mget", |parse| Ok(Command::Mget(Mget::from_frame(parse)?)));
+        parsers.insert("getdel", |parse| {
+            Ok(Command::GetDel(GetDel::from_frame(parse)?))
+        });
+        parsers.insert("publish", |parse| {
+            Ok(Command::Publish(Publish::from_frame(parse)?))
+        });
+        parsers.insert("set", |parse| Ok(Command::Set(Set::from_frame(parse)?)));
+        parsers.insert("setnx", |parse| {
+            Ok(Command::SetNx(SetNx::from_frame(parse)?))
+        });
+        parsers.insert("mset", |parse| Ok(Command::Mset(Mset::from_frame(parse)?)));
+        parsers.insert("append", |parse| {
+            Ok(Command::Append(Append::from_frame(parse)?))
+        });
+        parsers.insert("subscribe", |parse| {
+            Ok(Command::Subscribe(Subscribe::from_frame(parse)?))
+        });
+        parsers.insert("unsubscribe", |parse| {
+            Ok(Command::Unsubscribe(Unsubscribe::from_frame(parse)?))
+        });
+        parsers.insert("ping", |parse| Ok(Command::Ping(Ping::from_frame(parse)?)));
+        parsers.insert("hello", |parse| {
+            Ok(Command::Hello(Hello::from_frame(parse)?))
+        });
+        parsers.insert("info", |parse| Ok(Command::Info(Info::from_frame(parse)?)));
+        parsers.insert("replicaof", |parse| {
+            Ok(Command::ReplicaOf(ReplicaOf::from_frame(parse)?))
+        });
+        parsers.insert("slaveof", |parse| {
+            Ok(Command::ReplicaOf(ReplicaOf::from_frame(parse)?))
+        });
+        parsers.insert("lolwut", |parse| {
+            Ok(Command::Lolwut(Lolwut::from_frame(parse)?))
+        });
+        parsers.insert("config", |parse| {
+            Ok(Command::Config(Config::from_frame(parse)?))
+        });
+        parsers.insert("hotkeys", |parse| {
+            Ok(Command::Hotkeys(Hotkeys::from_frame(parse)?))
+        });
+        parsers.insert("expire", |parse| {
+            Ok(Command::Expire(Expire::from_frame(parse, false)?))
+        });
+        parsers.insert("pexpire", |parse| {
+            Ok(Command::Expire(Expire::from_frame(parse, true)?))
+        });
+        parsers.insert("expiretime", |parse| {
+            Ok(Command::ExpireTime(ExpireTime::from_frame(parse, false)?))
+        });
+        parsers.insert("pexpiretime", |parse| {
+            Ok(Command::ExpireTime(ExpireTime::from_frame(parse, true)?))
+        });
+        parsers.insert("exists", |parse| {
+            Ok(Command::Exists(Exists::from_frame(parse)?))
+        });
+        parsers.insert("incr", |parse| {
+            Ok(Command::Incr(Incr::from_frame(parse, 1)?))
+        });
+        parsers.insert("decr", |parse| {
+            Ok(Command::Incr(Incr::from_frame(parse, -1)?))
+        });
+        parsers.insert("persist", |parse| {
+            Ok(Command::Persist(Persist::from_frame(parse)?))
+        });
+        parsers.insert("debug", |parse| {
+            Ok(Command::Debug(Debug::from_frame(parse)?))
+        });
+        parsers.insert("cluster", |parse| {
+            Ok(Command::Cluster(Cluster::from_frame(parse)?))
+        });
+        parsers.insert("xadd", |parse| Ok(Command::XAdd(XAdd::from_frame(parse)?)));
+        parsers.insert("xlen", |parse| Ok(Command::XLen(XLen::from_frame(parse)?)));
+        parsers.insert("xrange", |parse| {
+            Ok(Command::XRange(XRange::from_frame(parse)?))
+        });
+        parsers.insert("xread", |parse| {
+            Ok(Command::XRead(XRead::from_frame(parse)?))
+        });
+        parsers
+    })
+}
+
 pub struct Parse {
     frames: IntoIter<Frame>,
 }
@@ -106,7 +452,7 @@ impl Parse {
     }
 
     pub fn next_string(&mut self) -> crate::Result<Option<String>> {
-        if let Some(frame) = self.next() {
+        if let Some(frame) = self.next_frame() {
             match frame {
                 Frame::Simple(s) => Ok(Some(s)),
                 Frame::Bulk(bytes) => Ok(Some(String::from_utf8(bytes.to_vec())?)),
@@ -121,12 +467,12 @@ impl Parse {
         }
     }
 
-    pub fn next(&mut self) -> Option<Frame> {
+    pub fn next_frame(&mut self) -> Option<Frame> {
         self.frames.next()
     }
 
     pub fn next_bytes(&mut self) -> crate::Result<Option<Bytes>> {
-        let frame = match self.next() {
+        let frame = match self.next_frame() {
             Some(frame) => frame,
             None => return Ok(None),
         };
@@ -145,7 +491,7 @@ impl Parse {
     pub fn next_int(&mut self) -> crate::Result<Option<i64>> {
         use atoi::atoi;
 
-        let frame = match self.next() {
+        let frame = match self.next_frame() {
             Some(frame) => frame,
             None => return Ok(None),
         };
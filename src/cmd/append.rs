@@ -0,0 +1,49 @@
+use bytes::Bytes;
+
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Append a value to the string held by a key, creating the key if needed,
+/// and reply with the new length of the string.
+pub struct Append {
+    key: String,
+    value: Bytes,
+}
+
+impl Append {
+    pub fn new(key: impl ToString, value: Bytes) -> Append {
+        Append {
+            key: key.to_string(),
+            value,
+        }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<Append> {
+        let key = match parse.next_string()? {
+            Some(key) => key,
+            None => return Err("protocol error: expected key".into()),
+        };
+
+        match parse.next_bytes()? {
+            Some(value) => Ok(Append { key, value }),
+            None => Err("protocol error: expected value".into()),
+        }
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db).unwrap_or_else(Frame::from);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        let len = db
+            .append(&self.key, self.value)
+            .map_err(CommandError::from_error)?;
+
+        Ok(Frame::Integer(len as i64))
+    }
+}
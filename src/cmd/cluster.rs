@@ -0,0 +1,73 @@
+use bytes::Bytes;
+
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::Parse;
+
+/// Report a single node with cluster support disabled, so that cluster-aware
+/// clients can talk to the server in non-cluster mode.
+///
+/// - `CLUSTER INFO` replies with the cluster state fields, including
+///   `cluster_enabled:0`.
+/// - `CLUSTER SLOTS` and `CLUSTER SHARDS` reply with an empty array, as no
+///   slot is assigned.
+/// - `CLUSTER NODES` replies with the line of this node alone.
+/// - `CLUSTER MYID` replies with the ID of this node, fixed for the lifetime
+///   of the server.
+pub enum Cluster {
+    Info,
+    Slots,
+    Shards,
+    Nodes,
+    MyId,
+}
+
+impl Cluster {
+    pub fn from_frame(mut parse: Parse) -> crate::Result<Cluster> {
+        let subcommand = match parse.next_string()? {
+            Some(subcommand) => subcommand,
+            None => return Err("protocol error; expected CLUSTER subcommand".into()),
+        };
+
+        match subcommand.to_lowercase().as_str() {
+            "info" => Ok(Cluster::Info),
+            "slots" => Ok(Cluster::Slots),
+            "shards" => Ok(Cluster::Shards),
+            "nodes" => Ok(Cluster::Nodes),
+            "myid" => Ok(Cluster::MyId),
+            _ => Err(format!("protocol error; unknown CLUSTER subcommand {}", subcommand).into()),
+        }
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = match self {
+            Cluster::Info => {
+                let info = [
+                    "cluster_enabled:0",
+                    "cluster_state:ok",
+                    "cluster_slots_assigned:0",
+                    "cluster_slots_ok:0",
+                    "cluster_slots_pfail:0",
+                    "cluster_slots_fail:0",
+                    "cluster_known_nodes:1",
+                    "cluster_size:0",
+                    "cluster_current_epoch:0",
+                    "cluster_my_epoch:0",
+                    "cluster_stats_messages_sent:0",
+                    "cluster_stats_messages_received:0",
+                ];
+                Frame::Bulk(Bytes::from(info.join("\r\n") + "\r\n"))
+            }
+            Cluster::Slots | Cluster::Shards => Frame::array(),
+            Cluster::Nodes => {
+                let line = format!("{} :0@0 myself,master - 0 0 0 connected\n", db.node_id());
+                Frame::Bulk(Bytes::from(line))
+            }
+            Cluster::MyId => Frame::Bulk(Bytes::copy_from_slice(db.node_id().as_bytes())),
+        };
+
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
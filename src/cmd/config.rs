@@ -0,0 +1,64 @@
+use bytes::Bytes;
+
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Inspect or change server settings.
+///
+/// - `CONFIG GET parameter` replies with a flat array of names and values.
+/// - `CONFIG SET parameter value` replies `OK`, or an error if the parameter
+///   is unknown or the value invalid.
+pub enum Config {
+    Get(String),
+    Set(String, String),
+}
+
+impl Config {
+    pub fn from_frame(mut parse: Parse) -> crate::Result<Config> {
+        let subcommand = match parse.next_string()? {
+            Some(subcommand) => subcommand,
+            None => return Err("protocol error; expected CONFIG subcommand".into()),
+        };
+
+        match subcommand.to_lowercase().as_str() {
+            "get" => match parse.next_string()? {
+                Some(name) => Ok(Config::Get(name)),
+                None => Err("protocol error; expected parameter for CONFIG GET".into()),
+            },
+            "set" => {
+                let name = match parse.next_string()? {
+                    Some(name) => name,
+                    None => return Err("protocol error; expected parameter for CONFIG SET".into()),
+                };
+
+                match parse.next_string()? {
+                    Some(value) => Ok(Config::Set(name, value)),
+                    None => Err("protocol error; expected value for CONFIG SET".into()),
+                }
+            }
+            _ => Err(format!("protocol error; unknown CONFIG subcommand {}", subcommand).into()),
+        }
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = match self {
+            Config::Get(pattern) => {
+                let mut response = Frame::array();
+                for (name, value) in db.settings().get_matching(&pattern) {
+                    response.push_bulk(Bytes::from(name));
+                    response.push_bulk(Bytes::from(value));
+                }
+                response
+            }
+            Config::Set(name, value) => match db.update_settings(|s| s.set(&name, &value)) {
+                Ok(()) => Frame::Simple("OK".to_string()),
+                Err(err) => CommandError::Err(format!("CONFIG SET failed - {}", err)).into(),
+            },
+        };
+
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
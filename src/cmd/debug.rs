@@ -0,0 +1,94 @@
+use bytes::Bytes;
+
+use crate::{connection::Connection, db::Db, frame::Frame, glob};
+
+use super::Parse;
+
+/// mini-redis extension commands for debugging the server.
+///
+/// - `DEBUG EXPIRATIONS [count]` replies with a flat array of keys and the
+///   milliseconds left before they expire, in the order the background task
+///   removes them.
+/// - `DEBUG STRINGMATCH-LEN pattern string` replies `1` if `string` matches
+///   the glob-style `pattern`, `0` otherwise.
+/// - `DEBUG DUMPKEY key` replies with the type, encoding, length and time to
+///   live of `key`, one `field:value` per line, or `Null` if it does not
+///   exist.
+pub enum Debug {
+    Expirations(usize),
+    StringMatch(Bytes, Bytes),
+    DumpKey(String),
+}
+
+/// Number of expirations reported when no count is given.
+const DEFAULT_COUNT: usize = 10;
+
+impl Debug {
+    pub fn from_frame(mut parse: Parse) -> crate::Result<Debug> {
+        let subcommand = match parse.next_string()? {
+            Some(subcommand) => subcommand,
+            None => return Err("protocol error; expected DEBUG subcommand".into()),
+        };
+
+        match subcommand.to_lowercase().as_str() {
+            "expirations" => {
+                let count = match parse.next_int()? {
+                    Some(count) => count.try_into()?,
+                    None => DEFAULT_COUNT,
+                };
+
+                Ok(Debug::Expirations(count))
+            }
+            "stringmatch-len" => {
+                let pattern = match parse.next_bytes()? {
+                    Some(pattern) => pattern,
+                    None => return Err("protocol error; expected pattern".into()),
+                };
+
+                match parse.next_bytes()? {
+                    Some(string) => Ok(Debug::StringMatch(pattern, string)),
+                    None => Err("protocol error; expected string".into()),
+                }
+            }
+            "dumpkey" => match parse.next_string()? {
+                Some(key) => Ok(Debug::DumpKey(key)),
+                None => Err("protocol error; expected key".into()),
+            },
+            _ => Err(format!("protocol error; unknown DEBUG subcommand {}", subcommand).into()),
+        }
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = match self {
+            Debug::Expirations(count) => {
+                let mut response = Frame::array();
+                for (key, ttl) in db.expirations(count) {
+                    response.push_bulk(Bytes::from(key));
+                    response.push_int(ttl.as_millis() as i64);
+                }
+                response
+            }
+            Debug::StringMatch(pattern, string) => {
+                Frame::Integer(glob::matches(&pattern, &string, false) as i64)
+            }
+            Debug::DumpKey(key) => match db.describe(&key) {
+                Some(info) => {
+                    let ttl = match info.ttl {
+                        Some(ttl) => ttl.as_millis() as i64,
+                        None => -1,
+                    };
+                    let dump = format!(
+                        "type:{}\r\nencoding:{}\r\nlength:{}\r\nttl_ms:{}\r\n",
+                        info.kind, info.encoding, info.len, ttl
+                    );
+                    Frame::Bulk(Bytes::from(dump))
+                }
+                None => Frame::Null,
+            },
+        };
+
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
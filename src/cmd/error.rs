@@ -0,0 +1,144 @@
+use std::fmt;
+
+use crate::frame::Frame;
+
+/// An error replied to a client. Each variant is rendered with the prefix
+/// Redis gives it, which clients match on to tell errors apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    /// A generic error, with its message.
+    Err(String),
+    /// The key holds another type of value than the command works on.
+    WrongType,
+    /// The connection must authenticate first.
+    NoAuth,
+    /// A transaction was discarded because of an error while queuing it.
+    ExecAbort,
+    /// The dataset is still being loaded.
+    Loading,
+    /// The user lacks a permission, with the message naming it.
+    NoPerm(String),
+    /// A write was sent to a read-only replica.
+    ReadOnly,
+    /// The target key of a command already exists.
+    BusyKey,
+    /// `HELLO` asked for a protocol version the server does not speak.
+    NoProto,
+}
+
+impl CommandError {
+    /// Returns the prefix of the error on the wire.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            CommandError::Err(_) => "ERR",
+            CommandError::WrongType => "WRONGTYPE",
+            CommandError::NoAuth => "NOAUTH",
+            CommandError::ExecAbort => "EXECABORT",
+            CommandError::Loading => "LOADING",
+            CommandError::NoPerm(_) => "NOPERM",
+            CommandError::ReadOnly => "READONLY",
+            CommandError::BusyKey => "BUSYKEY",
+            CommandError::NoProto => "NOPROTO",
+        }
+    }
+
+    /// The error replied when a command is given too few or too many
+    /// arguments.
+    pub(crate) fn wrong_arity(name: &str) -> CommandError {
+        CommandError::Err(format!("wrong number of arguments for '{}' command", name))
+    }
+
+    /// Convert an error returned by the database. Errors other than a
+    /// `CommandError` become generic errors.
+    pub(crate) fn from_error(err: crate::Error) -> CommandError {
+        match err.downcast::<CommandError>() {
+            Ok(err) => *err,
+            Err(err) => CommandError::Err(err.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            CommandError::Err(msg) | CommandError::NoPerm(msg) => msg,
+            CommandError::WrongType => "Operation against a key holding the wrong kind of value",
+            CommandError::NoAuth => "Authentication required.",
+            CommandError::ExecAbort => "Transaction discarded because of previous errors.",
+            CommandError::Loading => "Redis is loading the dataset in memory",
+            CommandError::ReadOnly => "You can't write against a read only replica.",
+            CommandError::BusyKey => "Target key name already exists.",
+            CommandError::NoProto => "unsupported protocol version",
+        };
+
+        write!(fmt, "{} {}", self.prefix(), msg)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<CommandError> for Frame {
+    fn from(err: CommandError) -> Frame {
+        Frame::Error(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variants_render_their_prefix() {
+        let errors = [
+            (
+                CommandError::Err("syntax error".to_string()),
+                "ERR syntax error",
+            ),
+            (
+                CommandError::WrongType,
+                "WRONGTYPE Operation against a key holding the wrong kind of value",
+            ),
+            (CommandError::NoAuth, "NOAUTH Authentication required."),
+            (
+                CommandError::ExecAbort,
+                "EXECABORT Transaction discarded because of previous errors.",
+            ),
+            (
+                CommandError::Loading,
+                "LOADING Redis is loading the dataset in memory",
+            ),
+            (
+                CommandError::NoPerm("this user has no permissions".to_string()),
+                "NOPERM this user has no permissions",
+            ),
+            (
+                CommandError::ReadOnly,
+                "READONLY You can't write against a read only replica.",
+            ),
+            (
+                CommandError::BusyKey,
+                "BUSYKEY Target key name already exists.",
+            ),
+            (
+                CommandError::NoProto,
+                "NOPROTO unsupported protocol version",
+            ),
+        ];
+
+        for (err, expected) in errors {
+            assert_eq!(Frame::Error(expected.to_string()), Frame::from(err));
+        }
+    }
+
+    #[test]
+    fn from_error_keeps_command_errors() {
+        let err: crate::Error = CommandError::WrongType.into();
+        assert_eq!(CommandError::WrongType, CommandError::from_error(err));
+
+        let err: crate::Error = "value is not an integer".into();
+        assert_eq!(
+            CommandError::Err("value is not an integer".to_string()),
+            CommandError::from_error(err)
+        );
+    }
+}
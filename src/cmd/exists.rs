@@ -0,0 +1,42 @@
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Reply with the number of the given keys that exist. A key given more than
+/// once is counted as many times.
+pub struct Exists {
+    keys: Vec<String>,
+}
+
+impl Exists {
+    pub fn new(keys: Vec<String>) -> Exists {
+        Exists { keys }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<Exists> {
+        let mut keys = Vec::new();
+        while let Some(key) = parse.next_string()? {
+            keys.push(key);
+        }
+
+        if keys.is_empty() {
+            return Err(CommandError::wrong_arity("exists").into());
+        }
+
+        Ok(Exists { keys })
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db).unwrap_or_else(Frame::from);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        let count = self.keys.iter().filter(|key| db.exists(key)).count();
+
+        Ok(Frame::Integer(count as i64))
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Set a key to expire after a number of seconds for `EXPIRE`, or of
+/// milliseconds for `PEXPIRE`, replacing any expiration it had.
+///
+/// Replies `1` if the expiration was set and `0` if the key does not exist. A
+/// timeout of `0` or less expires the key right away.
+pub struct Expire {
+    key: String,
+    timeout: i64,
+    millis: bool,
+}
+
+impl Expire {
+    pub fn new(key: impl ToString, timeout: i64, millis: bool) -> Expire {
+        Expire {
+            key: key.to_string(),
+            timeout,
+            millis,
+        }
+    }
+
+    pub fn from_frame(mut parse: Parse, millis: bool) -> crate::Result<Expire> {
+        let key = match parse.next_string()? {
+            Some(key) => key,
+            None => return Err("protocol error: expected key".into()),
+        };
+
+        match parse.next_int()? {
+            Some(timeout) => Ok(Expire {
+                key,
+                timeout,
+                millis,
+            }),
+            None => Err("protocol error: expected timeout".into()),
+        }
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db).unwrap_or_else(Frame::from);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        let ms = if self.millis {
+            Some(self.timeout)
+        } else {
+            self.timeout.checked_mul(1000)
+        };
+        let ms = match ms {
+            Some(ms) => ms.max(0) as u64,
+            None => {
+                return Err(CommandError::Err(format!(
+                    "invalid expire time in '{}' command",
+                    self.get_name()
+                )))
+            }
+        };
+
+        let set = db.expire(&self.key, Duration::from_millis(ms));
+
+        Ok(Frame::Integer(set as i64))
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        if self.millis {
+            "pexpire"
+        } else {
+            "expire"
+        }
+    }
+}
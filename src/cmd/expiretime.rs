@@ -0,0 +1,53 @@
+use std::time::UNIX_EPOCH;
+
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::Parse;
+
+/// Reply with the Unix time at which a key expires: in seconds for
+/// `EXPIRETIME`, in milliseconds for `PEXPIRETIME`.
+///
+/// Replies `-1` if the key has no expiration and `-2` if it does not exist.
+pub struct ExpireTime {
+    key: String,
+    millis: bool,
+}
+
+impl ExpireTime {
+    pub fn new(key: impl ToString, millis: bool) -> ExpireTime {
+        ExpireTime {
+            key: key.to_string(),
+            millis,
+        }
+    }
+
+    pub fn from_frame(mut parse: Parse, millis: bool) -> crate::Result<ExpireTime> {
+        match parse.next_string()? {
+            Some(key) => Ok(ExpireTime { key, millis }),
+            None => Err("protocol error: expected key".into()),
+        }
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = match db.expire_time(&self.key) {
+            Some(Some(when)) => {
+                let ms = when.duration_since(UNIX_EPOCH)?.as_millis() as i64;
+                Frame::Integer(if self.millis { ms } else { ms / 1000 })
+            }
+            Some(None) => Frame::Integer(-1),
+            None => Frame::Integer(-2),
+        };
+
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        if self.millis {
+            "pexpiretime"
+        } else {
+            "expiretime"
+        }
+    }
+}
@@ -1,8 +1,6 @@
-use bytes::Bytes;
-
 use crate::{connection::Connection, db::Db, frame::Frame};
 
-use super::Parse;
+use super::{CommandError, Parse};
 
 pub struct Get {
     key: String,
@@ -18,25 +16,26 @@ impl Get {
     pub fn from_frame(mut parse: Parse) -> crate::Result<Get> {
         match parse.next_string()? {
             Some(key) => Ok(Get { key }),
-            None => return Err("protocol error: expected key".into()),
+            None => Err("protocol error: expected key".into()),
         }
     }
 
     pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
-        let response = match db.get(&self.key) {
-            Some(entry) => Frame::Bulk(entry),
-            None => Frame::Null,
-        };
-
+        let response = self.execute(db).unwrap_or_else(Frame::from);
         conn.write_frame(&response).await?;
 
         Ok(())
     }
 
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        match db.get(&self.key).map_err(CommandError::from_error)? {
+            Some(entry) => Ok(Frame::Bulk(entry)),
+            None => Ok(Frame::Null),
+        }
+    }
+
     pub(crate) fn into_frame(self) -> Frame {
-        let mut frame = Frame::array();
-        frame.push_bulk(Bytes::from("get".as_bytes()));
-        frame.push_bulk(Bytes::from(self.key.into_bytes()));
-        frame
+        Frame::cmd("get").arg(self.key).build()
     }
 }
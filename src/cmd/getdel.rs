@@ -0,0 +1,38 @@
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Reply with the value of a key and remove the key, atomically.
+pub struct GetDel {
+    key: String,
+}
+
+impl GetDel {
+    pub fn new(key: impl ToString) -> GetDel {
+        GetDel {
+            key: key.to_string(),
+        }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<GetDel> {
+        match parse.next_string()? {
+            Some(key) => Ok(GetDel { key }),
+            None => Err("protocol error: expected key".into()),
+        }
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db).unwrap_or_else(Frame::from);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        match db.get_del(&self.key).map_err(CommandError::from_error)? {
+            Some(value) => Ok(Frame::Bulk(value)),
+            None => Ok(Frame::Null),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use bytes::Bytes;
+
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Switch the connection to protocol version 2 or 3 and reply with a map
+/// describing the server.
+///
+/// Without a version, the protocol is left as is. Under RESP2 the map is
+/// written as a flat array of alternating keys and values.
+pub struct Hello {
+    version: Option<i64>,
+}
+
+impl Hello {
+    pub fn new(version: Option<i64>) -> Hello {
+        Hello { version }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<Hello> {
+        let version = parse.next_int()?;
+
+        if parse.next_frame().is_some() {
+            return Err("protocol error; HELLO options are not supported".into());
+        }
+
+        Ok(Hello { version })
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let version = match self.version {
+            None => conn.protocol(),
+            Some(version @ (2 | 3)) => version as u8,
+            Some(_) => {
+                conn.write_frame(&CommandError::NoProto.into()).await?;
+                return Ok(());
+            }
+        };
+        conn.set_protocol(version);
+
+        let role = match db.master() {
+            Some(_) => "replica",
+            None => "master",
+        };
+
+        let field = |name: &'static str| Frame::Bulk(Bytes::from_static(name.as_bytes()));
+        let response = Frame::Map(vec![
+            (field("server"), field("redis")),
+            (field("version"), field(crate::VERSION)),
+            (field("proto"), Frame::Integer(version as i64)),
+            (field("mode"), field("standalone")),
+            (field("role"), field(role)),
+            (field("modules"), Frame::Array(vec![])),
+        ]);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
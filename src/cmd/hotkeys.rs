@@ -0,0 +1,43 @@
+use bytes::Bytes;
+
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::Parse;
+
+/// Reply with the most accessed keys as a flat array of keys and access
+/// counts, most accessed first.
+///
+/// Accesses are only counted while `hotkeys-tracking` is enabled.
+pub struct Hotkeys {
+    count: usize,
+}
+
+/// Number of keys reported when no count is given.
+const DEFAULT_COUNT: usize = 10;
+
+impl Hotkeys {
+    pub fn new(count: usize) -> Hotkeys {
+        Hotkeys { count }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<Hotkeys> {
+        let count = match parse.next_int()? {
+            Some(count) => count.try_into()?,
+            None => DEFAULT_COUNT,
+        };
+
+        Ok(Hotkeys { count })
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let mut response = Frame::array();
+        for (key, hits) in db.hotkeys(self.count) {
+            response.push_bulk(Bytes::from(key));
+            response.push_int(hits as i64);
+        }
+
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
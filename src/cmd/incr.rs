@@ -0,0 +1,50 @@
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Add one to the integer held by a key for `INCR`, or subtract one for
+/// `DECR`, and reply with the result. A missing key counts as `0`.
+pub struct Incr {
+    key: String,
+    delta: i64,
+}
+
+impl Incr {
+    pub fn new(key: impl ToString, delta: i64) -> Incr {
+        Incr {
+            key: key.to_string(),
+            delta,
+        }
+    }
+
+    pub fn from_frame(mut parse: Parse, delta: i64) -> crate::Result<Incr> {
+        match parse.next_string()? {
+            Some(key) => Ok(Incr { key, delta }),
+            None => Err("protocol error: expected key".into()),
+        }
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db).unwrap_or_else(Frame::from);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        let value = db
+            .incr_by(&self.key, self.delta)
+            .map_err(CommandError::from_error)?;
+
+        Ok(Frame::Integer(value))
+    }
+
+    pub(crate) fn get_name(&self) -> &str {
+        if self.delta < 0 {
+            "decr"
+        } else {
+            "incr"
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt::Write;
+
+use bytes::Bytes;
+
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::Parse;
+
+/// Report information about the server as `field:value` lines grouped under
+/// `# Section` headers.
+///
+/// Only the `server` and `replication` sections are supported. Without a
+/// section, all of them are returned.
+pub struct Info {
+    section: Option<String>,
+}
+
+impl Info {
+    pub fn new(section: Option<String>) -> Info {
+        Info { section }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<Info> {
+        let section = parse.next_string()?.map(|s| s.to_lowercase());
+        Ok(Info { section })
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let all = matches!(self.section.as_deref(), None | Some("all" | "everything"));
+        let mut info = String::new();
+
+        if all || self.section.as_deref() == Some("server") {
+            info.push_str("# Server\r\n");
+            write!(info, "redis_version:{}\r\n", crate::VERSION)?;
+        }
+
+        if all || self.section.as_deref() == Some("replication") {
+            if !info.is_empty() {
+                info.push_str("\r\n");
+            }
+
+            info.push_str("# Replication\r\n");
+            match db.master() {
+                Some((host, port)) => {
+                    info.push_str("role:slave\r\n");
+                    write!(info, "master_host:{}\r\n", host)?;
+                    write!(info, "master_port:{}\r\n", port)?;
+                    info.push_str("master_link_status:down\r\n");
+                }
+                None => {
+                    info.push_str("role:master\r\n");
+                    info.push_str("connected_slaves:0\r\n");
+                }
+            }
+        }
+
+        let response = Frame::Bulk(Bytes::from(info));
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use bytes::Bytes;
+
+use crate::{connection::Connection, frame::Frame};
+
+use super::Parse;
+
+/// Reply with a small piece of generated art followed by the server version.
+///
+/// `LOLWUT VERSION n` is accepted for compatibility, but the art is the same
+/// for every version.
+pub struct Lolwut;
+
+/// Side length of the generated square.
+const SIZE: usize = 8;
+
+impl Lolwut {
+    pub fn new() -> Lolwut {
+        Lolwut
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<Lolwut> {
+        if let Some(option) = parse.next_string()? {
+            if !option.eq_ignore_ascii_case("version") {
+                return Err(format!("protocol error; unexpected LOLWUT option {}", option).into());
+            }
+
+            if parse.next_int()?.is_none() {
+                return Err("protocol error; expected a number for VERSION".into());
+            }
+        }
+
+        Ok(Lolwut)
+    }
+
+    pub async fn apply(self, conn: &mut Connection) -> crate::Result<()> {
+        let mut art = String::new();
+
+        // A checkerboard that grows denser towards the bottom right corner.
+        for row in 0..SIZE {
+            for col in 0..SIZE {
+                let c = if (row + col) % 2 == 0 && row * col >= SIZE {
+                    '#'
+                } else if (row + col) % 2 == 0 {
+                    '+'
+                } else {
+                    ' '
+                };
+                art.push(c);
+            }
+            art.push('\n');
+        }
+
+        art.push_str(&format!("\nmini-redis ver. {}\n", crate::VERSION));
+
+        let response = Frame::Bulk(Bytes::from(art));
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
+
+impl Default for Lolwut {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Reply with the value of each of the given keys, in order, as an array.
+/// Missing keys and keys holding another type of value are `Null`.
+pub struct Mget {
+    keys: Vec<String>,
+}
+
+impl Mget {
+    pub fn new(keys: Vec<String>) -> Mget {
+        Mget { keys }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<Mget> {
+        let mut keys = Vec::new();
+        while let Some(key) = parse.next_string()? {
+            keys.push(key);
+        }
+
+        if keys.is_empty() {
+            return Err(CommandError::wrong_arity("mget").into());
+        }
+
+        Ok(Mget { keys })
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db).unwrap_or_else(Frame::from);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        let values = db
+            .mget(&self.keys)
+            .into_iter()
+            .map(|value| value.map_or(Frame::Null, Frame::Bulk))
+            .collect();
+
+        Ok(Frame::Array(values))
+    }
+}
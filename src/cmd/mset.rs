@@ -0,0 +1,46 @@
+use bytes::Bytes;
+
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Store several values at once, each at its key, without expiration.
+pub struct Mset {
+    pairs: Vec<(String, Bytes)>,
+}
+
+impl Mset {
+    pub fn new(pairs: Vec<(String, Bytes)>) -> Mset {
+        Mset { pairs }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<Mset> {
+        let mut pairs = Vec::new();
+        while let Some(key) = parse.next_string()? {
+            match parse.next_bytes()? {
+                Some(value) => pairs.push((key, value)),
+                None => return Err(CommandError::wrong_arity("mset").into()),
+            }
+        }
+
+        if pairs.is_empty() {
+            return Err(CommandError::wrong_arity("mset").into());
+        }
+
+        Ok(Mset { pairs })
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db).unwrap_or_else(Frame::from);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        db.mset(self.pairs);
+
+        Ok(Frame::Simple("OK".to_string()))
+    }
+}
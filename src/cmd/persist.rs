@@ -0,0 +1,40 @@
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Remove the expiration of a key, so that it is kept until deleted.
+///
+/// Replies `1` if the expiration was removed and `0` if the key does not exist
+/// or has no expiration.
+pub struct Persist {
+    key: String,
+}
+
+impl Persist {
+    pub fn new(key: impl ToString) -> Persist {
+        Persist {
+            key: key.to_string(),
+        }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<Persist> {
+        match parse.next_string()? {
+            Some(key) => Ok(Persist { key }),
+            None => Err("protocol error: expected key".into()),
+        }
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db).unwrap_or_else(Frame::from);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        let removed = db.persist(&self.key);
+
+        Ok(Frame::Integer(removed as i64))
+    }
+}
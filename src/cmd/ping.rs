@@ -31,12 +31,19 @@ impl Ping {
         Ok(())
     }
 
+    /// Reply as Redis does to a connection subscribed to channels: with a
+    /// `pong` message carrying the given message, or an empty one.
+    pub(crate) async fn apply_subscribed(self, conn: &mut Connection) -> crate::Result<()> {
+        let mut response = Frame::array();
+        response.push_bulk(Bytes::from_static(b"pong"));
+        response.push_bulk(self.msg.unwrap_or_default());
+
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
     pub(crate) fn into_frame(self) -> Frame {
-        let mut frame = Frame::array();
-        frame.push_bulk(Bytes::from("ping".as_bytes()));
-        if let Some(msg) = self.msg {
-            frame.push_bulk(msg);
-        }
-        frame
+        Frame::cmd("ping").args(self.msg).build()
     }
 }
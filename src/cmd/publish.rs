@@ -2,7 +2,7 @@ use bytes::Bytes;
 
 use crate::{connection::Connection, db::Db, frame::Frame};
 
-use super::Parse;
+use super::{CommandError, Parse};
 
 pub struct Publish {
     channel: String,
@@ -32,20 +32,23 @@ impl Publish {
     }
 
     pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
-        let num_subscribers = db.publish(self.channel, self.message);
-
-        let response = Frame::Integer(num_subscribers as i64);
+        let response = self.execute(db).unwrap_or_else(Frame::from);
         conn.write_frame(&response).await?;
 
         Ok(())
     }
 
-    pub(crate) fn into_frame(self) -> Frame {
-        let mut frame = Frame::array();
-        frame.push_bulk(Bytes::from("publish".as_bytes()));
-        frame.push_bulk(Bytes::from(self.channel.into_bytes()));
-        frame.push_bulk(self.message);
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        let num_subscribers = db.publish(self.channel, self.message);
 
-        frame
+        Ok(Frame::Integer(num_subscribers as i64))
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        Frame::cmd("publish")
+            .arg(self.channel)
+            .arg(self.message)
+            .build()
     }
 }
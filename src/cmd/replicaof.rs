@@ -0,0 +1,55 @@
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Make the server a replica of another instance, or turn it back into a
+/// master with `REPLICAOF NO ONE`.
+///
+/// Only the declared master is recorded so that `INFO replication` reports a
+/// consistent role. No data is replicated.
+pub struct ReplicaOf {
+    master: Option<(String, u16)>,
+}
+
+impl ReplicaOf {
+    pub fn new(master: Option<(String, u16)>) -> ReplicaOf {
+        ReplicaOf { master }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<ReplicaOf> {
+        let host = match parse.next_string()? {
+            Some(host) => host,
+            None => return Err("protocol error; expected host or NO ONE".into()),
+        };
+
+        let port = match parse.next_string()? {
+            Some(port) => port,
+            None => return Err("protocol error; expected port or NO ONE".into()),
+        };
+
+        if host.eq_ignore_ascii_case("no") && port.eq_ignore_ascii_case("one") {
+            return Ok(ReplicaOf { master: None });
+        }
+
+        let port = match port.parse() {
+            Ok(port) => port,
+            Err(_) => {
+                let msg = "value is not an integer or out of range".to_string();
+                return Err(CommandError::Err(msg).into());
+            }
+        };
+
+        Ok(ReplicaOf {
+            master: Some((host, port)),
+        })
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        db.set_master(self.master);
+
+        let response = Frame::Simple("OK".to_string());
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+}
@@ -1,15 +1,29 @@
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use bytes::Bytes;
 
-use crate::{connection::Connection, db::Db, frame::Frame};
+use crate::{
+    connection::Connection,
+    db::{Db, SetIf},
+    frame::Frame,
+};
 
-use super::Parse;
+use super::{CommandError, Parse};
 
+/// Store a value at a key, with options:
+///
+/// - `EX seconds`, `PX milliseconds`, `EXAT timestamp`, `PXAT ms-timestamp`:
+///   expire the key.
+/// - `NX`: only store if the key does not exist. `XX`: only if it does.
+/// - `GET`: reply with the value the key held before, instead of `OK`.
+///
+/// Without `GET`, replies `Null` if `NX` or `XX` prevented storing the value.
 pub struct Set {
     key: String,
     value: Bytes,
     expire: Option<Duration>,
+    condition: SetIf,
+    get: bool,
 }
 
 impl Set {
@@ -18,6 +32,8 @@ impl Set {
             key: key.to_string(),
             value,
             expire,
+            condition: SetIf::Always,
+            get: false,
         }
     }
 
@@ -32,38 +48,51 @@ impl Set {
             None => return Err("protocol error: expected value".into()),
         };
 
-        let expire = match parse.next_string()? {
-            Some(s) => match s.as_str() {
-                "EX" => match parse.next_int()? {
-                    Some(secs) => Some(Duration::from_secs(secs.try_into()?)),
-                    None => return Err("protocol error; expected seconds for EX".into()),
-                },
-                "PX" => match parse.next_int()? {
-                    Some(secs) => Some(Duration::from_millis(secs.try_into()?)),
-                    None => return Err("protocol error; expected seconds for EX".into()),
-                },
-                _ => return Err("currently `SET` only supports the expiration option".into()),
-            },
-            None => None,
-        };
+        let mut set = Set::new(key, value, None);
+
+        while let Some(option) = parse.next_string()? {
+            match option.to_uppercase().as_str() {
+                "EX" | "PX" | "EXAT" | "PXAT" if set.expire.is_some() => return Err(syntax_error()),
+                "EX" => set.expire = Some(Duration::from_secs(expire_time(&mut parse)?)),
+                "PX" => set.expire = Some(Duration::from_millis(expire_time(&mut parse)?)),
+                "EXAT" => set.expire = Some(until(Duration::from_secs(expire_time(&mut parse)?))),
+                "PXAT" => set.expire = Some(until(Duration::from_millis(expire_time(&mut parse)?))),
+                "NX" | "XX" if set.condition != SetIf::Always => return Err(syntax_error()),
+                "NX" => set.condition = SetIf::Absent,
+                "XX" => set.condition = SetIf::Present,
+                "GET" => set.get = true,
+                _ => return Err(syntax_error()),
+            }
+        }
 
-        Ok(Set { key, value, expire })
+        Ok(set)
     }
 
     pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
-        db.set(self.key, self.value, self.expire);
-
-        let response = Frame::Simple("OK".to_string());
+        let response = self.execute(db).unwrap_or_else(Frame::from);
         conn.write_frame(&response).await?;
 
         Ok(())
     }
 
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        let (stored, old) = db
+            .set_if(self.key, self.value, self.expire, self.condition, self.get)
+            .map_err(CommandError::from_error)?;
+
+        let response = if self.get {
+            old.map_or(Frame::Null, Frame::Bulk)
+        } else if stored {
+            Frame::Simple("OK".to_string())
+        } else {
+            Frame::Null
+        };
+        Ok(response)
+    }
+
     pub(crate) fn into_frame(self) -> Frame {
-        let mut frame = Frame::array();
-        frame.push_bulk(Bytes::from("set".as_bytes()));
-        frame.push_bulk(Bytes::from(self.key.into_bytes()));
-        frame.push_bulk(self.value);
+        let mut frame = Frame::cmd("set").arg(self.key).arg(self.value);
         if let Some(ms) = self.expire {
             // Expirations in Redis procotol can be specified in two ways
             // 1. SET key value EX seconds
@@ -71,9 +100,40 @@ impl Set {
             // We the second option because it allows greater precision and
             // src/bin/cli.rs parses the expiration argument as milliseconds
             // in duration_from_ms_str()
-            frame.push_bulk(Bytes::from("px".as_bytes()));
-            frame.push_int(ms.as_millis() as i64);
+            frame = frame.arg("px").arg_int(ms.as_millis() as i64);
+        }
+        match self.condition {
+            SetIf::Always => {}
+            SetIf::Absent => frame = frame.arg("nx"),
+            SetIf::Present => frame = frame.arg("xx"),
         }
-        frame
+        if self.get {
+            frame = frame.arg("get");
+        }
+        frame.build()
+    }
+}
+
+/// Error replied for missing, unknown or contradictory options.
+fn syntax_error() -> crate::Error {
+    CommandError::Err("syntax error".to_string()).into()
+}
+
+/// Read the argument of an expiration option, which must not be negative.
+fn expire_time(parse: &mut Parse) -> crate::Result<u64> {
+    match parse.next_int()? {
+        Some(time) => time.try_into().map_err(|_| {
+            CommandError::Err("invalid expire time in 'set' command".to_string()).into()
+        }),
+        None => Err(syntax_error()),
     }
 }
+
+/// Time left until `timestamp`, a duration since the Unix epoch. A timestamp
+/// in the past expires the key right away.
+fn until(timestamp: Duration) -> Duration {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO);
+    timestamp.saturating_sub(now)
+}
@@ -0,0 +1,49 @@
+use bytes::Bytes;
+
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Store a value at a key only if the key does not exist, the legacy form of
+/// `SET key value NX`. Never sets an expiration.
+///
+/// Replies `1` if the value was stored and `0` if the key already existed.
+pub struct SetNx {
+    key: String,
+    value: Bytes,
+}
+
+impl SetNx {
+    pub fn new(key: impl ToString, value: Bytes) -> SetNx {
+        SetNx {
+            key: key.to_string(),
+            value,
+        }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<SetNx> {
+        let key = match parse.next_string()? {
+            Some(key) => key,
+            None => return Err("protocol error: expected key".into()),
+        };
+
+        match parse.next_bytes()? {
+            Some(value) => Ok(SetNx { key, value }),
+            None => Err("protocol error: expected value".into()),
+        }
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db).unwrap_or_else(Frame::from);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        let stored = db.set_nx(self.key, self.value);
+
+        Ok(Frame::Integer(stored as i64))
+    }
+}
@@ -1,4 +1,4 @@
-use std::{pin::Pin, vec};
+use std::{collections::VecDeque, pin::Pin, vec};
 
 use bytes::Bytes;
 use tokio::sync::broadcast;
@@ -6,7 +6,7 @@ use tokio_stream::{Stream, StreamExt, StreamMap};
 
 use crate::{connection::Connection, db::Db, frame::Frame, shutdown::Shutdown};
 
-use super::{unknown::Unknown, Command, Parse};
+use super::{Command, CommandError, Flags, Parse};
 
 pub struct Subscribe {
     channels: Vec<String>,
@@ -39,42 +39,112 @@ impl Subscribe {
     ) -> crate::Result<()> {
         let mut subscriptions = StreamMap::new();
 
-        for channel in self.channels {
-            subscribe_channel(&mut subscriptions, channel, db, conn).await?;
+        let res = subscribe_loop(self.channels, &mut subscriptions, db, conn, shutdown).await;
+
+        // Dropping the streams drops their receivers; release the channels
+        // left without subscribers.
+        let channels: Vec<_> = subscriptions.keys().cloned().collect();
+        drop(subscriptions);
+        for channel in channels {
+            db.unsubscribe(&channel);
+        }
+
+        res
+    }
+
+    pub(crate) fn into_frame(self) -> Frame {
+        Frame::cmd("subscribe").args(self.channels).build()
+    }
+}
+
+async fn subscribe_loop(
+    channels: Vec<String>,
+    subscriptions: &mut StreamMap<String, Message>,
+    db: &Db,
+    conn: &mut Connection,
+    shutdown: &mut Shutdown,
+) -> crate::Result<()> {
+    for channel in channels {
+        subscribe_channel(subscriptions, channel, db, conn).await?;
+    }
+
+    let limit = db.settings().pubsub_lag_limit;
+
+    loop {
+        tokio::select! {
+            Some((channel, msg)) = subscriptions.next() => {
+                let response = message_frame(channel, msg?);
+
+                // A subscriber that stopped reading blocks the write; shutting
+                // down must not wait for it.
+                let write = async {
+                    if limit > 0 {
+                        write_messages(response, subscriptions, conn, limit).await
+                    } else {
+                        Ok(conn.write_frame(&response).await?)
+                    }
+                };
+                tokio::select! {
+                    res = write => res?,
+                    _ = shutdown.recv() => return Ok(()),
+                }
+            }
+            res = conn.read_frame() => {
+                let frame = match res? {
+                    Some(frame) => frame,
+                    // This happens if the remote connection is closed.
+                    None => return Ok(()),
+                };
+
+                handle_command(frame, subscriptions, db, conn).await?;
+            }
+            _ = shutdown.recv() => return Ok(()),
         }
+    }
+}
+
+/// Write `response` and every message received while it is being written.
+///
+/// Messages keep being drained from `subscriptions` while a write is blocked
+/// on a slow client and wait in an output buffer. Once more than `limit`
+/// messages are waiting, the subscriber is disconnected.
+async fn write_messages(
+    response: Frame,
+    subscriptions: &mut StreamMap<String, Message>,
+    conn: &mut Connection,
+    limit: usize,
+) -> crate::Result<()> {
+    let mut pending = VecDeque::from([response]);
+
+    while let Some(response) = pending.pop_front() {
+        let write = conn.write_frame(&response);
+        tokio::pin!(write);
 
         loop {
             tokio::select! {
+                res = &mut write => break res?,
                 Some((channel, msg)) = subscriptions.next() => {
-                    let mut response = Frame::Array(vec![]);
-                    response.push_bulk(Bytes::from_static(b"message"));
-                    response.push_bulk(Bytes::from(channel));
-                    response.push_bulk(msg);
+                    pending.push_back(message_frame(channel, msg?));
 
-                    conn.write_frame(&response).await?;
-                }
-                res = conn.read_frame() => {
-                    let frame = match res? {
-                        Some(frame) => frame,
-                        // This happens if the remote connection is closed.
-                        None => return Ok(()),
-                    };
-
-                    handle_command(frame, &mut subscriptions, db, conn).await?;
+                    if pending.len() > limit {
+                        return Err(lagging(limit));
+                    }
                 }
-                _ = shutdown.recv() => return Ok(()),
             }
         }
     }
 
-    pub(crate) fn into_frame(self) -> Frame {
-        let mut frame = Frame::array();
-        frame.push_bulk(Bytes::from("subscribe".as_bytes()));
-        for channel in self.channels {
-            frame.push_bulk(Bytes::from(channel.into_bytes()));
-        }
-        frame
-    }
+    Ok(())
+}
+
+/// The frame delivering `msg`. Like every pub/sub reply, it is a push, which
+/// RESP2 connections write as a plain array.
+fn message_frame(channel: String, msg: Bytes) -> Frame {
+    let mut response = Frame::Push(vec![]);
+    response.push_bulk(Bytes::from_static(b"message"));
+    response.push_bulk(Bytes::from(channel));
+    response.push_bulk(msg);
+    response
 }
 
 async fn subscribe_channel(
@@ -83,12 +153,42 @@ async fn subscribe_channel(
     db: &Db,
     conn: &mut Connection,
 ) -> crate::Result<()> {
-    let mut rx = db.subscribe(&channel);
+    // Going over a limit fails this subscription only. The connection and its
+    // other subscriptions are left untouched.
+    let settings = db.settings();
+    let max = settings.max_subscriptions_per_client;
+    if max > 0 && subscriptions.len() >= max && !subscriptions.contains_key(&channel) {
+        let response = Frame::from(CommandError::Err(
+            "max number of subscriptions reached".to_string(),
+        ));
+        conn.write_frame(&response).await?;
+        return Ok(());
+    }
+
+    // The receiver is registered before the acknowledgment is written, so no
+    // message published after this point is missed. Those messages buffer in
+    // `rx` and are only delivered once the caller goes back to polling
+    // `subscriptions`, which always happens after the acknowledgment below has
+    // been written. The acknowledgment thus precedes every message on the
+    // channel.
+    let mut rx = match db.subscribe(&channel) {
+        Ok(rx) => rx,
+        Err(err) => {
+            let response = Frame::from(CommandError::from_error(err));
+            conn.write_frame(&response).await?;
+            return Ok(());
+        }
+    };
+    let limit = settings.pubsub_lag_limit;
 
     let stream = Box::pin(async_stream::stream! {
         loop {
             match rx.recv().await {
-                Ok(val) => yield val,
+                Ok(val) => yield Ok(val),
+                Err(broadcast::error::RecvError::Lagged(_)) if limit > 0 => {
+                    yield Err(lagging(limit));
+                    break;
+                }
                 Err(broadcast::error::RecvError::Lagged(_)) => {},
                 Err(_) => break,
             }
@@ -97,7 +197,7 @@ async fn subscribe_channel(
 
     subscriptions.insert(channel.clone(), stream);
 
-    let mut response = Frame::Array(vec![]);
+    let mut response = Frame::Push(vec![]);
     response.push_bulk(Bytes::from_static(b"subscribe"));
     response.push_bulk(Bytes::from(channel));
     response.push_int(subscriptions.len() as i64);
@@ -107,7 +207,17 @@ async fn subscribe_channel(
     Ok(())
 }
 
-type Message = Pin<Box<dyn Stream<Item = Bytes> + Send>>;
+/// The error disconnecting a subscriber that fell more than `limit` messages
+/// behind.
+fn lagging(limit: usize) -> crate::Error {
+    format!(
+        "subscriber disconnected; more than {} messages behind (client-output-buffer-limit)",
+        limit
+    )
+    .into()
+}
+
+type Message = Pin<Box<dyn Stream<Item = crate::Result<Bytes>> + Send>>;
 
 async fn handle_command(
     frame: Frame,
@@ -115,10 +225,29 @@ async fn handle_command(
     db: &Db,
     conn: &mut Connection,
 ) -> crate::Result<()> {
-    let cmd = Command::from_frame(frame)?;
+    let cmd = match Command::from_frame_or_reply(frame)? {
+        Ok(cmd) => cmd,
+        Err(err) => {
+            conn.write_frame(&Frame::from(err)).await?;
+            return Ok(());
+        }
+    };
+
+    if let Command::Unknown(cmd) = cmd {
+        return cmd.apply(conn).await;
+    }
+
+    // Only the commands flagged as allowed in subscribe mode are permitted in
+    // this context.
+    if !cmd.flags().contains(Flags::ALLOWED_IN_SUBSCRIBE) {
+        let response = Frame::from(CommandError::Err(format!(
+            "Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+            cmd.get_name()
+        )));
+        conn.write_frame(&response).await?;
+        return Ok(());
+    }
 
-    // Only `SUBSCRIBE` and `UNSUBSCRIBE` commands are permitted
-    // in this context.
     match cmd {
         Command::Subscribe(Subscribe { channels }) => {
             for channel in channels {
@@ -132,8 +261,9 @@ async fn handle_command(
 
             for channel in channels {
                 subscriptions.remove(&channel);
+                db.unsubscribe(&channel);
 
-                let mut response = Frame::Array(vec![]);
+                let mut response = Frame::Push(vec![]);
                 response.push_bulk(Bytes::from_static(b"unsubscribe"));
                 response.push_bulk(Bytes::from(channel));
                 response.push_int(subscriptions.len() as i64);
@@ -141,9 +271,9 @@ async fn handle_command(
                 conn.write_frame(&response).await?;
             }
         }
+        Command::Ping(cmd) => cmd.apply_subscribed(conn).await?,
         command => {
-            let cmd = Unknown::new(command.get_name());
-            cmd.apply(conn).await?;
+            return Err(format!("`{}` is unsupported in subscribe mode", command.get_name()).into())
         }
     }
     Ok(())
@@ -171,13 +301,6 @@ impl Unsubscribe {
     }
 
     pub(crate) fn into_frame(self) -> Frame {
-        let mut frame = Frame::array();
-        frame.push_bulk(Bytes::from("unsubscribe".as_bytes()));
-
-        for channel in self.channels {
-            frame.push_bulk(Bytes::from(channel.into_bytes()));
-        }
-
-        frame
+        Frame::cmd("unsubscribe").args(self.channels).build()
     }
 }
@@ -1,18 +1,57 @@
+use std::fmt::Write;
+
 use crate::{connection::Connection, frame::Frame};
 
+use super::{CommandError, Parse};
+
+/// Length of the echoed arguments above which no more are added, as in Redis.
+const MAX_ARGS_LEN: usize = 128;
+
 pub struct Unknown {
     command_name: String,
+    args: Vec<String>,
 }
 
 impl Unknown {
     pub fn new(name: impl ToString) -> Self {
         Self {
             command_name: name.to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Capture the arguments of the unknown command `name` so that they are
+    /// echoed in the error.
+    pub fn from_frame(name: impl ToString, mut parse: Parse) -> Self {
+        let mut args = Vec::new();
+        while let Some(frame) = parse.next_frame() {
+            args.push(frame.to_string());
+        }
+
+        Self {
+            command_name: name.to_string(),
+            args,
         }
     }
 
     pub async fn apply(self, conn: &mut Connection) -> crate::Result<()> {
-        let response = Frame::Error(format!("ERR unknown command '{}'", self.command_name));
+        let mut msg = format!(
+            "unknown command '{}', with args beginning with: ",
+            self.command_name
+        );
+
+        let mut len = 0;
+        for arg in &self.args {
+            if len >= MAX_ARGS_LEN {
+                break;
+            }
+
+            let arg: String = arg.chars().take(MAX_ARGS_LEN - len).collect();
+            len += arg.len() + 3;
+            write!(msg, "'{}' ", arg)?;
+        }
+
+        let response = Frame::from(CommandError::Err(msg));
 
         conn.write_frame(&response).await?;
 
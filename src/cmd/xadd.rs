@@ -0,0 +1,71 @@
+use bytes::Bytes;
+
+use crate::{
+    connection::Connection,
+    db::Db,
+    frame::Frame,
+    stream::{Fields, NewId},
+};
+
+use super::{CommandError, Parse};
+
+/// Append an entry to a stream, creating the stream if it does not exist.
+///
+/// The ID is given as `*` to generate it, as `<ms>-*` to generate only the
+/// sequence number, or in full. Replies with the ID of the new entry.
+pub struct XAdd {
+    key: String,
+    id: String,
+    fields: Fields,
+}
+
+impl XAdd {
+    pub fn new(key: impl ToString, id: impl ToString, fields: Fields) -> XAdd {
+        XAdd {
+            key: key.to_string(),
+            id: id.to_string(),
+            fields,
+        }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<XAdd> {
+        let key = match parse.next_string()? {
+            Some(key) => key,
+            None => return Err("protocol error: expected key".into()),
+        };
+
+        let id = match parse.next_string()? {
+            Some(id) => id,
+            None => return Err("protocol error: expected ID".into()),
+        };
+
+        let mut fields = Vec::new();
+        while let Some(field) = parse.next_bytes()? {
+            match parse.next_bytes()? {
+                Some(value) => fields.push((field, value)),
+                None => return Err("protocol error: expected value".into()),
+            }
+        }
+        if fields.is_empty() {
+            return Err("protocol error: expected field".into());
+        }
+
+        Ok(XAdd { key, id, fields })
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db).unwrap_or_else(Frame::from);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        let id = NewId::parse(&self.id)
+            .and_then(|id| db.xadd(self.key, id, self.fields))
+            .map_err(CommandError::from_error)?;
+
+        Ok(Frame::Bulk(Bytes::from(id.to_string())))
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{connection::Connection, db::Db, frame::Frame};
+
+use super::{CommandError, Parse};
+
+/// Reply with the number of entries in a stream, or `0` if the key does not
+/// exist.
+pub struct XLen {
+    key: String,
+}
+
+impl XLen {
+    pub fn new(key: impl ToString) -> XLen {
+        XLen {
+            key: key.to_string(),
+        }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<XLen> {
+        match parse.next_string()? {
+            Some(key) => Ok(XLen { key }),
+            None => Err("protocol error: expected key".into()),
+        }
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db).unwrap_or_else(Frame::from);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        let len = db.xlen(&self.key).map_err(CommandError::from_error)?;
+
+        Ok(Frame::Integer(len as i64))
+    }
+}
@@ -0,0 +1,98 @@
+use bytes::Bytes;
+
+use crate::{
+    connection::Connection,
+    db::Db,
+    frame::Frame,
+    stream::{Fields, StreamId},
+};
+
+use super::{CommandError, Parse};
+
+/// Reply with the entries of a stream with IDs between `start` and `end`
+/// inclusive, oldest first.
+///
+/// `-` and `+` stand for the lowest and highest possible IDs. `COUNT n`
+/// limits the reply to the first `n` entries.
+pub struct XRange {
+    key: String,
+    start: String,
+    end: String,
+    count: Option<usize>,
+}
+
+impl XRange {
+    pub fn new(
+        key: impl ToString,
+        start: impl ToString,
+        end: impl ToString,
+        count: Option<usize>,
+    ) -> XRange {
+        XRange {
+            key: key.to_string(),
+            start: start.to_string(),
+            end: end.to_string(),
+            count,
+        }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<XRange> {
+        let key = match parse.next_string()? {
+            Some(key) => key,
+            None => return Err("protocol error: expected key".into()),
+        };
+
+        let (start, end) = match (parse.next_string()?, parse.next_string()?) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return Err("protocol error: expected start and end".into()),
+        };
+
+        let count = match parse.next_string()? {
+            Some(s) if s.eq_ignore_ascii_case("count") => match parse.next_int()? {
+                Some(count) => Some(count.max(0) as usize),
+                None => return Err("protocol error: expected count".into()),
+            },
+            Some(_) => return Err("protocol error: `XRANGE` only supports COUNT".into()),
+            None => None,
+        };
+
+        Ok(XRange {
+            key,
+            start,
+            end,
+            count,
+        })
+    }
+
+    pub async fn apply(self, db: &Db, conn: &mut Connection) -> crate::Result<()> {
+        let response = self.execute(db).unwrap_or_else(Frame::from);
+        conn.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Run the command against `db` and return the response.
+    pub(crate) fn execute(self, db: &Db) -> Result<Frame, CommandError> {
+        let entries = StreamId::parse_start(&self.start)
+            .and_then(|start| {
+                let end = StreamId::parse_end(&self.end)?;
+                db.xrange(&self.key, start, end, self.count)
+            })
+            .map_err(CommandError::from_error)?;
+
+        Ok(Frame::Array(entries.into_iter().map(entry_frame).collect()))
+    }
+}
+
+/// Encode a stream entry as `[id, [field, value, ...]]`.
+pub(crate) fn entry_frame((id, fields): (StreamId, Fields)) -> Frame {
+    let fields = fields
+        .into_iter()
+        .flat_map(|(field, value)| [Frame::Bulk(field), Frame::Bulk(value)])
+        .collect();
+
+    Frame::Array(vec![
+        Frame::Bulk(Bytes::from(id.to_string())),
+        Frame::Array(fields),
+    ])
+}
@@ -0,0 +1,183 @@
+use std::future::{poll_fn, Future};
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::sync::Notify;
+use tokio::time::{self, Instant};
+
+use crate::{connection::Connection, db::Db, frame::Frame, shutdown::Shutdown, stream::StreamId};
+
+use super::{xrange::entry_frame, CommandError, Parse};
+
+/// Reply with the entries added to each stream after the given ID, as an
+/// array of `[key, entries]` pairs. Replies `NullArray` when there are none.
+///
+/// With `BLOCK ms`, waits up to `ms` milliseconds (forever for `0`) for an
+/// entry to be added to any of the streams. The `$` ID stands for the last
+/// entry of the stream when the command runs, so that only entries added
+/// while blocked are returned.
+pub struct XRead {
+    /// Stream keys paired with the ID to read after.
+    streams: Vec<(String, String)>,
+    count: Option<usize>,
+    block: Option<Duration>,
+}
+
+impl XRead {
+    pub fn new(
+        streams: Vec<(String, String)>,
+        count: Option<usize>,
+        block: Option<Duration>,
+    ) -> XRead {
+        XRead {
+            streams,
+            count,
+            block,
+        }
+    }
+
+    pub fn from_frame(mut parse: Parse) -> crate::Result<XRead> {
+        let mut count = None;
+        let mut block = None;
+
+        loop {
+            match parse.next_string()? {
+                Some(s) if s.eq_ignore_ascii_case("count") => match parse.next_int()? {
+                    Some(n) => count = Some(n.max(0) as usize),
+                    None => return Err("protocol error: expected count".into()),
+                },
+                Some(s) if s.eq_ignore_ascii_case("block") => match parse.next_int()? {
+                    Some(ms) => block = Some(Duration::from_millis(ms.try_into()?)),
+                    None => return Err("protocol error: expected timeout for BLOCK".into()),
+                },
+                Some(s) if s.eq_ignore_ascii_case("streams") => break,
+                _ => return Err("protocol error: expected STREAMS".into()),
+            }
+        }
+
+        let mut args = Vec::new();
+        while let Some(arg) = parse.next_string()? {
+            args.push(arg);
+        }
+        if args.is_empty() || args.len() % 2 != 0 {
+            return Err("protocol error: expected an ID for each stream".into());
+        }
+
+        let ids = args.split_off(args.len() / 2);
+        let streams = args.into_iter().zip(ids).collect();
+
+        Ok(XRead {
+            streams,
+            count,
+            block,
+        })
+    }
+
+    pub async fn apply(
+        self,
+        db: &Db,
+        conn: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let mut streams = Vec::with_capacity(self.streams.len());
+        for (key, id) in self.streams {
+            let id = match id.as_str() {
+                "$" => db.xlast_id(&key),
+                _ => StreamId::parse_start(&id),
+            };
+            match id {
+                Ok(id) => streams.push((key, id)),
+                Err(err) => {
+                    let response = Frame::from(CommandError::from_error(err));
+                    conn.write_frame(&response).await?;
+                    return Ok(());
+                }
+            }
+        }
+
+        let response = read_loop(&streams, self.count, self.block, db, shutdown).await;
+
+        // The notifiers of the loop have been dropped; release the ones no
+        // other client waits on.
+        if self.block.is_some() {
+            for (key, _) in &streams {
+                db.release_stream_notify(key);
+            }
+        }
+
+        // Nothing is written if the server is shutting down.
+        if let Some(response) = response {
+            conn.write_frame(&response).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Read the streams, waiting for new entries if `block` is set. Returns
+/// `None` on shutdown.
+async fn read_loop(
+    streams: &[(String, StreamId)],
+    count: Option<usize>,
+    block: Option<Duration>,
+    db: &Db,
+    shutdown: &mut Shutdown,
+) -> Option<Frame> {
+    let deadline = block
+        .filter(|block| !block.is_zero())
+        .map(|block| Instant::now() + block);
+
+    loop {
+        // Get the notifiers before reading, so that an entry added right
+        // after the read still wakes this client.
+        let notifies: Vec<Arc<Notify>> = match block {
+            Some(_) => streams
+                .iter()
+                .map(|(key, _)| db.stream_notify(key))
+                .collect(),
+            None => Vec::new(),
+        };
+        let mut notified: Vec<_> = notifies.iter().map(|n| Box::pin(n.notified())).collect();
+
+        match db.xread(streams, count) {
+            Ok(read) if !read.is_empty() => {
+                let frames = read
+                    .into_iter()
+                    .map(|(key, entries)| {
+                        let entries = entries.into_iter().map(entry_frame).collect();
+                        Frame::Array(vec![Frame::Bulk(Bytes::from(key)), Frame::Array(entries)])
+                    })
+                    .collect();
+                return Some(Frame::Array(frames));
+            }
+            Ok(_) if block.is_none() => return Some(Frame::NullArray),
+            Ok(_) => {}
+            Err(err) => return Some(CommandError::from_error(err).into()),
+        }
+
+        let any_notified = poll_fn(|cx| {
+            for notified in notified.iter_mut() {
+                if notified.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(());
+                }
+            }
+            Poll::Pending
+        });
+
+        tokio::select! {
+            _ = any_notified => {}
+            _ = timeout(deadline) => return Some(Frame::NullArray),
+            _ = shutdown.recv() => return None,
+        }
+    }
+}
+
+/// Completes at `deadline`, or never without one.
+async fn timeout(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,243 @@
+//! Server settings that can be inspected and changed at runtime with the
+//! `CONFIG` command.
+
+use crate::glob;
+
+/// The tunable server settings.
+///
+/// Each setting is exposed under a Redis-style parameter name, e.g.
+/// `hotkeys-tracking`.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Count accesses per key so that `HOTKEYS` can rank them. Off by default
+    /// as it turns every read into a write to the entry.
+    pub hotkeys_tracking: bool,
+
+    /// Number of bytes buffered across all connections above which reads of
+    /// partial frames are paused. `0` disables the limit. Applies to
+    /// connections accepted after it is set.
+    pub read_buffer_total_limit: usize,
+
+    /// How the background task removes expired keys.
+    pub active_expire: ActiveExpire,
+
+    /// Number of messages that may wait to be written to a slow subscriber
+    /// before it is disconnected. `0` disables the limit, in which case a
+    /// subscriber falling too far behind silently skips messages. Applies to
+    /// `SUBSCRIBE` commands issued after it is set.
+    pub pubsub_lag_limit: usize,
+
+    /// Number of channels a single connection may be subscribed to. `0`
+    /// disables the limit.
+    pub max_subscriptions_per_client: usize,
+
+    /// Number of distinct channels with subscribers across the server. `0`
+    /// disables the limit.
+    pub max_pubsub_channels: usize,
+
+    /// Number of seconds after which a connection is closed, however active
+    /// it is. `0` disables the limit. Applies to connections accepted after
+    /// it is set.
+    pub max_session_duration: u64,
+
+    /// Length in bytes above which a bulk string sent by a client is a
+    /// protocol error. Applies to connections accepted after it is set.
+    pub proto_max_bulk_len: usize,
+
+    /// Log every frame read from and written to a client at the `trace`
+    /// level. Applies to connections accepted after it is set.
+    pub verbose_frame_logging: bool,
+
+    /// Number of worker tasks running commands that only need the database,
+    /// fed through a bounded queue by the connections. `0` runs every command
+    /// on its connection's task. Applies to connections accepted after it is
+    /// set.
+    pub command_workers: usize,
+
+    /// Number of bytes a connection may skip to get past corrupt input: on a
+    /// protocol error, the input is skipped up to the next array at the start
+    /// of a line, and reading resumes from there. `0` disconnects on the first
+    /// protocol error. Applies to connections accepted after it is set.
+    pub proto_recovery_limit: usize,
+
+    /// Number of connections accepted per second. Connections arriving faster,
+    /// e.g. all clients reconnecting after a restart, wait in the listen
+    /// backlog and are accepted evenly spaced. `0` disables the limit.
+    pub max_accept_rate: u64,
+}
+
+/// Strategy used by the background task to remove expired keys.
+///
+/// `Precise` suits most workloads: keys are removed right at their deadline
+/// and the task sleeps whenever nothing is due. As every due key is removed
+/// while holding the state lock, a large number of keys expiring at the same
+/// instant stalls all other commands for the length of the sweep.
+///
+/// `Sampling` trades timeliness for bounded pauses, as the active expiration
+/// cycle of Redis: a key may linger past its deadline until it is picked or
+/// accessed, and the task wakes periodically even when idle. Prefer it when
+/// many keys share the same or very close deadlines, e.g. a bulk load with a
+/// common TTL.
+///
+/// With 1M keys expiring over 100ms (`benches/expiration.rs`, one core),
+/// `Precise` removed the last key at its deadline, 600ms in. `Sampling` took
+/// about 30s: a cycle removes some 4,000 keys within its 25ms budget, while
+/// never holding the lock for more than a sample of 20 keys. Keys it has not
+/// reached yet are still removed when accessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActiveExpire {
+    /// Wake at the earliest expiration and remove every due key at once.
+    Precise,
+    /// Wake on a fixed interval and check 20 random keys with an expiration,
+    /// removing the expired ones. Repeat, releasing the lock in between, while
+    /// more than a quarter of the sample was expired and the cycle has time
+    /// left.
+    Sampling,
+}
+
+/// Names of all the parameters, in the order `CONFIG GET *` lists them.
+const PARAMETERS: &[&str] = &[
+    "hotkeys-tracking",
+    "read-buffer-total-limit",
+    "active-expire-mode",
+    "client-output-buffer-limit",
+    "max-subscriptions-per-client",
+    "max-pubsub-channels",
+    "max-session-duration",
+    "proto-max-bulk-len",
+    "verbose-frame-logging",
+    "command-workers",
+    "proto-recovery-limit",
+    "max-accept-rate",
+];
+
+impl Settings {
+    pub fn new() -> Settings {
+        Settings {
+            hotkeys_tracking: false,
+            read_buffer_total_limit: 0,
+            active_expire: ActiveExpire::Precise,
+            pubsub_lag_limit: 0,
+            max_subscriptions_per_client: 0,
+            max_pubsub_channels: 0,
+            max_session_duration: 0,
+            proto_max_bulk_len: crate::frame::DEFAULT_MAX_BULK_LEN,
+            verbose_frame_logging: false,
+            command_workers: 0,
+            proto_recovery_limit: 0,
+            max_accept_rate: 0,
+        }
+    }
+
+    /// Returns the value of parameter `name`, or `None` if there is no such
+    /// parameter.
+    pub fn get(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "hotkeys-tracking" => yes_no(self.hotkeys_tracking),
+            "read-buffer-total-limit" => self.read_buffer_total_limit.to_string(),
+            "active-expire-mode" => match self.active_expire {
+                ActiveExpire::Precise => "precise".to_string(),
+                ActiveExpire::Sampling => "sampling".to_string(),
+            },
+            "client-output-buffer-limit" => format!("pubsub {}", self.pubsub_lag_limit),
+            "max-subscriptions-per-client" => self.max_subscriptions_per_client.to_string(),
+            "max-pubsub-channels" => self.max_pubsub_channels.to_string(),
+            "max-session-duration" => self.max_session_duration.to_string(),
+            "proto-max-bulk-len" => self.proto_max_bulk_len.to_string(),
+            "verbose-frame-logging" => yes_no(self.verbose_frame_logging),
+            "command-workers" => self.command_workers.to_string(),
+            "proto-recovery-limit" => self.proto_recovery_limit.to_string(),
+            "max-accept-rate" => self.max_accept_rate.to_string(),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    /// Returns every parameter matching the glob-style `pattern` with its
+    /// value. Case is ignored.
+    pub fn get_matching(&self, pattern: &str) -> Vec<(&'static str, String)> {
+        PARAMETERS
+            .iter()
+            .filter(|&&name| glob::matches(pattern.as_bytes(), name.as_bytes(), true))
+            .map(|&name| (name, self.get(name).unwrap()))
+            .collect()
+    }
+
+    /// Set parameter `name` to `value`.
+    pub fn set(&mut self, name: &str, value: &str) -> crate::Result<()> {
+        match name.to_lowercase().as_str() {
+            "hotkeys-tracking" => self.hotkeys_tracking = parse_yes_no(value)?,
+            "read-buffer-total-limit" => self.read_buffer_total_limit = value.parse()?,
+            "active-expire-mode" => {
+                self.active_expire = match value.to_lowercase().as_str() {
+                    "precise" => ActiveExpire::Precise,
+                    "sampling" => ActiveExpire::Sampling,
+                    _ => {
+                        return Err(format!(
+                            "argument must be 'precise' or 'sampling', got '{}'",
+                            value
+                        )
+                        .into())
+                    }
+                }
+            }
+            "client-output-buffer-limit" => {
+                self.pubsub_lag_limit = parse_output_buffer_limit(value)?
+            }
+            "max-subscriptions-per-client" => self.max_subscriptions_per_client = value.parse()?,
+            "max-pubsub-channels" => self.max_pubsub_channels = value.parse()?,
+            "max-session-duration" => self.max_session_duration = value.parse()?,
+            "proto-max-bulk-len" => self.proto_max_bulk_len = value.parse()?,
+            "verbose-frame-logging" => self.verbose_frame_logging = parse_yes_no(value)?,
+            "command-workers" => self.command_workers = value.parse()?,
+            "proto-recovery-limit" => self.proto_recovery_limit = value.parse()?,
+            "max-accept-rate" => self.max_accept_rate = value.parse()?,
+            _ => return Err(format!("Unknown option '{}'", name).into()),
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+fn parse_yes_no(value: &str) -> crate::Result<bool> {
+    match value.to_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(format!("argument must be 'yes' or 'no', got '{}'", value).into()),
+    }
+}
+
+/// Parse a `client-output-buffer-limit` value of the form `pubsub <messages>`.
+/// Only the `pubsub` class is supported.
+fn parse_output_buffer_limit(value: &str) -> crate::Result<usize> {
+    let mut parts = value.split_whitespace();
+
+    match parts.next() {
+        Some(class) if class.eq_ignore_ascii_case("pubsub") => {}
+        _ => return Err(format!("argument must be 'pubsub <messages>', got '{}'", value).into()),
+    }
+
+    let limit = match parts.next() {
+        Some(limit) => limit.parse()?,
+        None => {
+            return Err(format!("argument must be 'pubsub <messages>', got '{}'", value).into())
+        }
+    };
+
+    if parts.next().is_some() {
+        return Err(format!("argument must be 'pubsub <messages>', got '{}'", value).into());
+    }
+
+    Ok(limit)
+}
@@ -1,42 +1,236 @@
+use std::collections::BTreeSet;
 use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use bytes::{Buf, BytesMut};
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt, BufWriter},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter},
     net::TcpStream,
+    sync::Notify,
 };
+use tracing::{trace, warn};
 
 use crate::frame::{self, Frame};
 
-pub struct Connection {
-    stream: BufWriter<TcpStream>,
+pub struct Connection<S = TcpStream> {
+    stream: BufWriter<S>,
     buffer: BytesMut,
+    /// Server-wide accounting of buffered bytes, if reads are limited.
+    read_budget: Option<ReadBudget>,
+    /// Length above which bulk strings are rejected.
+    max_bulk_len: usize,
+    /// Address of the remote end, if it has one.
+    peer_addr: Option<SocketAddr>,
+    /// Log every frame read and written.
+    log_frames: bool,
+    /// Number of bytes that may be skipped after a protocol error, `0` if
+    /// protocol errors are not recovered from.
+    recovery_limit: usize,
+    /// Number of bytes skipped since the last frame read.
+    skipped: usize,
+    /// The protocol error being recovered from, while the start of the next
+    /// frame has not been received yet.
+    corrupt: Option<String>,
+    /// Number of frames no larger than `INITIAL_CAPACITY` read in a row.
+    small_frames: usize,
+    /// Protocol version of the frames written, `2` or `3`.
+    protocol: u8,
 }
 
-impl Connection {
-    pub fn new(socket: TcpStream) -> Connection {
+/// Capacity of the read buffer of a new connection.
+const INITIAL_CAPACITY: usize = 4 * 1024;
+
+/// Capacity above which the read buffer is shrunk back to `INITIAL_CAPACITY`
+/// once the connection goes back to small frames.
+const SHRINK_ABOVE: usize = 16 * INITIAL_CAPACITY;
+
+/// Number of small frames to read in a row before shrinking, so that a
+/// connection alternating between large and small frames keeps its buffer.
+const SHRINK_AFTER_SMALL_FRAMES: usize = 8;
+
+/// Returns whether `err` means the peer went away, e.g. a write to a socket it
+/// closed, rather than something going wrong on the server.
+pub(crate) fn is_disconnect(err: &crate::Error) -> bool {
+    match err.downcast_ref::<io::Error>() {
+        Some(err) => matches!(
+            err.kind(),
+            io::ErrorKind::BrokenPipe
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+        ),
+        None => false,
+    }
+}
+
+/// Tracks the bytes buffered but not yet parsed by every connection sharing
+/// it.
+#[derive(Debug, Default)]
+pub struct ReadBuffers {
+    used: AtomicUsize,
+    /// Notified whenever buffered bytes are released or a connection stops
+    /// waiting.
+    released: Notify,
+    /// Tickets of the connections paused with a partial frame, oldest first.
+    waiting: Mutex<BTreeSet<u64>>,
+    next_ticket: AtomicU64,
+}
+
+/// A connection's share of a `ReadBuffers`.
+struct ReadBudget {
+    buffers: Arc<ReadBuffers>,
+    /// Total number of buffered bytes above which reads are paused.
+    limit: usize,
+    /// The place of the connection among those paused, until its partial
+    /// frame is read.
+    ticket: Option<u64>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Connection<S> {
+    pub fn new(socket: S) -> Connection<S> {
         Connection {
             stream: BufWriter::new(socket),
-            buffer: BytesMut::with_capacity(4 * 1024),
+            buffer: BytesMut::with_capacity(INITIAL_CAPACITY),
+            read_budget: None,
+            max_bulk_len: frame::DEFAULT_MAX_BULK_LEN,
+            peer_addr: None,
+            log_frames: false,
+            recovery_limit: 0,
+            skipped: 0,
+            corrupt: None,
+            small_frames: 0,
+            protocol: 2,
+        }
+    }
+
+    /// Record `addr` as the address of the remote end.
+    pub fn with_peer_addr(mut self, addr: SocketAddr) -> Connection<S> {
+        self.peer_addr = Some(addr);
+        self
+    }
+
+    /// Returns the address of the remote end, or `unknown` if none was
+    /// recorded, e.g. for a Unix socket.
+    pub fn peer_addr(&self) -> String {
+        match self.peer_addr {
+            Some(addr) => addr.to_string(),
+            None => "unknown".to_string(),
         }
     }
 
+    /// Reject bulk strings longer than `max_bulk_len` with a protocol error.
+    pub fn with_max_bulk_len(mut self, max_bulk_len: usize) -> Connection<S> {
+        self.max_bulk_len = max_bulk_len;
+        self
+    }
+
+    /// Log every frame read and written at the `trace` level.
+    pub fn with_frame_logging(mut self, enabled: bool) -> Connection<S> {
+        self.log_frames = enabled;
+        self
+    }
+
+    /// On a protocol error, skip the input up to the next `*` at the start of
+    /// a line and resume reading from there, instead of failing. The error is
+    /// returned once more than `limit` bytes have been skipped without reading
+    /// a frame. A `limit` of `0` disables recovery.
+    pub fn with_recovery_limit(mut self, limit: usize) -> Connection<S> {
+        self.recovery_limit = limit;
+        self
+    }
+
+    /// Returns the protocol version of the frames written, as negotiated with
+    /// `HELLO`.
+    pub fn protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    /// Read and write frames in protocol `version`, `2` or `3`. Under RESP2,
+    /// reading a RESP3-only type is a protocol error, and those types are
+    /// written as their RESP2 counterparts: pushes as arrays, maps as flat
+    /// arrays, doubles and big numbers as bulk strings, booleans as integers.
+    pub fn set_protocol(&mut self, version: u8) {
+        self.protocol = version;
+    }
+
+    /// Account the bytes buffered by this connection in `buffers`.
+    ///
+    /// While the bytes buffered across all connections sharing `buffers` are
+    /// at or above `limit`, a connection holding a partial frame stops reading
+    /// until bytes are released. Connections with an empty buffer keep
+    /// reading, so small requests are still served while large ones are held
+    /// back. The paused connection with the oldest partial frame always keeps
+    /// reading, so that frames larger than `limit`, or partial frames adding
+    /// up to it, cannot stall every connection.
+    pub fn with_read_budget(mut self, buffers: Arc<ReadBuffers>, limit: usize) -> Connection<S> {
+        self.read_budget = Some(ReadBudget {
+            buffers,
+            limit,
+            ticket: None,
+        });
+        self
+    }
+
     pub async fn read_frame(&mut self) -> crate::Result<Option<Frame>> {
         loop {
-            if !self.buffer.is_empty() {
-                let mut buf = self.buffer.as_ref();
+            // The tail of corrupt input is discarded before parsing again, lest
+            // it be taken for an inline command.
+            if let Some(cause) = self.corrupt.take() {
+                let found = self.skip_to_next_frame();
+                if self.skipped > self.recovery_limit {
+                    return Err(cause.into());
+                }
+                if !found {
+                    self.corrupt = Some(cause);
+                }
+            }
 
-                match Frame::parse(&mut buf) {
+            if !self.buffer.is_empty() && self.corrupt.is_none() {
+                let buf = self.buffer.as_ref();
+
+                match Frame::parse_with_limit(buf, self.max_bulk_len, self.protocol) {
                     Ok((advance, frame)) => {
+                        let capacity = self.buffer.capacity();
                         self.buffer.advance(advance);
+                        self.maybe_shrink(capacity, advance);
+                        if let Some(budget) = &mut self.read_budget {
+                            budget.resume();
+                            budget.buffers.release(advance);
+                        }
+                        self.skipped = 0;
+                        if self.log_frames {
+                            trace!(peer = %self.peer_addr(), "read {}", frame);
+                        }
                         return Ok(Some(frame));
                     }
                     Err(frame::Error::Incomplete) => {}
-                    Err(frame::Error::Other(e)) => return Err(e),
+                    Err(frame::Error::Other(e)) if self.recovery_limit == 0 => return Err(e),
+                    Err(frame::Error::Other(e)) => {
+                        let found = self.skip_to_next_frame();
+                        if self.skipped > self.recovery_limit {
+                            return Err(e);
+                        }
+                        warn!(cause = %e, peer = %self.peer_addr(), skipped = self.skipped, "skipping corrupt input");
+
+                        if found {
+                            continue;
+                        }
+                        self.corrupt = Some(e.to_string());
+                    }
                 }
+
+                if let Some(budget) = &mut self.read_budget {
+                    budget.wait().await;
+                }
+            }
+
+            let n = self.stream.read_buf(&mut self.buffer).await?;
+            if let Some(budget) = &self.read_budget {
+                budget.buffers.acquire(n);
             }
 
-            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+            if 0 == n {
                 if self.buffer.is_empty() {
                     return Ok(None);
                 } else {
@@ -46,24 +240,58 @@ impl Connection {
         }
     }
 
+    /// Replace a read buffer that grew for large frames with a small one, once
+    /// `SHRINK_AFTER_SMALL_FRAMES` small frames were read since the last large
+    /// one. `capacity` is that of the buffer before the frame of `len` bytes
+    /// was consumed.
+    fn maybe_shrink(&mut self, capacity: usize, len: usize) {
+        if len > INITIAL_CAPACITY {
+            self.small_frames = 0;
+            return;
+        }
+        self.small_frames += 1;
+
+        if capacity <= SHRINK_ABOVE
+            || self.small_frames < SHRINK_AFTER_SMALL_FRAMES
+            || self.buffer.len() > INITIAL_CAPACITY
+        {
+            return;
+        }
+
+        let mut buffer = BytesMut::with_capacity(INITIAL_CAPACITY);
+        buffer.extend_from_slice(&self.buffer);
+        self.buffer = buffer;
+    }
+
+    /// Discard the buffered input up to the next `*` following a line ending.
+    /// Returns `false` if there is none yet, in which case everything but the
+    /// last two bytes, which may start a line ending, is discarded.
+    fn skip_to_next_frame(&mut self) -> bool {
+        let (skip, found) = match self.buffer.windows(3).position(|w| w == b"\r\n*") {
+            Some(i) => (i + 2, true),
+            None => (self.buffer.len().saturating_sub(2), false),
+        };
+
+        self.buffer.advance(skip);
+        if let Some(budget) = &self.read_budget {
+            budget.buffers.release(skip);
+        }
+        self.skipped += skip;
+
+        found
+    }
+
     pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
-        match frame {
-            Frame::Array(frames) => {
-                self.stream.write_u8(b'*').await?;
-                self.write_decimal(frames.len() as i64).await?;
-                for frame in frames {
-                    self.write_value(frame).await?;
-                }
-            }
-            _ => {
-                self.write_value(frame).await?;
-            }
+        if self.log_frames {
+            trace!(peer = %self.peer_addr(), "write {}", frame);
         }
 
+        self.write_value(frame).await?;
+
         self.stream.flush().await
     }
 
-    /// Write a non-array frame to the stream.
+    /// Write a frame to the stream, including the elements of nested arrays.
     async fn write_value(&mut self, frame: &Frame) -> io::Result<()> {
         match frame {
             Frame::Simple(s) => {
@@ -86,10 +314,71 @@ impl Connection {
                 self.stream.write_all(val).await?;
                 self.stream.write_all(b"\r\n").await?;
             }
+            Frame::Null | Frame::NullArray if self.protocol >= 3 => {
+                self.stream.write_all(b"_\r\n").await?;
+            }
             Frame::Null => {
                 self.stream.write_all(b"$-1\r\n").await?;
             }
-            Frame::Array(_) => unreachable!(),
+            Frame::NullArray => {
+                self.stream.write_all(b"*-1\r\n").await?;
+            }
+            Frame::Array(frames) | Frame::Push(frames) => {
+                let byte = match frame {
+                    Frame::Push(_) if self.protocol >= 3 => b'>',
+                    _ => b'*',
+                };
+                self.stream.write_u8(byte).await?;
+                self.write_decimal(frames.len() as i64).await?;
+                for frame in frames {
+                    // Recursive async calls must be boxed.
+                    Box::pin(self.write_value(frame)).await?;
+                }
+            }
+            Frame::Double(double) if self.protocol < 3 => {
+                let double = Frame::Bulk(frame::format_double(*double).into());
+                Box::pin(self.write_value(&double)).await?;
+            }
+            Frame::Boolean(value) if self.protocol < 3 => {
+                self.stream.write_u8(b':').await?;
+                self.write_decimal(*value as i64).await?;
+            }
+            Frame::Map(pairs) if self.protocol < 3 => {
+                self.stream.write_u8(b'*').await?;
+                self.write_decimal(2 * pairs.len() as i64).await?;
+                for (key, value) in pairs {
+                    Box::pin(self.write_value(key)).await?;
+                    Box::pin(self.write_value(value)).await?;
+                }
+            }
+            Frame::BigNumber(digits) if self.protocol < 3 => {
+                let digits = Frame::Bulk(digits.clone().into());
+                Box::pin(self.write_value(&digits)).await?;
+            }
+            Frame::Double(double) => {
+                self.stream.write_u8(b',').await?;
+                self.stream
+                    .write_all(frame::format_double(*double).as_bytes())
+                    .await?;
+                self.stream.write_all(b"\r\n").await?;
+            }
+            Frame::Boolean(value) => {
+                let line: &[u8] = if *value { b"#t\r\n" } else { b"#f\r\n" };
+                self.stream.write_all(line).await?;
+            }
+            Frame::Map(pairs) => {
+                self.stream.write_u8(b'%').await?;
+                self.write_decimal(pairs.len() as i64).await?;
+                for (key, value) in pairs {
+                    Box::pin(self.write_value(key)).await?;
+                    Box::pin(self.write_value(value)).await?;
+                }
+            }
+            Frame::BigNumber(digits) => {
+                self.stream.write_u8(b'(').await?;
+                self.stream.write_all(digits.as_bytes()).await?;
+                self.stream.write_all(b"\r\n").await?;
+            }
         }
 
         Ok(())
@@ -102,3 +391,581 @@ impl Connection {
         Ok(())
     }
 }
+
+impl<S> Drop for Connection<S> {
+    fn drop(&mut self) {
+        if let Some(budget) = &mut self.read_budget {
+            budget.resume();
+            budget.buffers.release(self.buffer.len());
+        }
+    }
+}
+
+impl ReadBudget {
+    /// Wait until fewer than `limit` bytes are buffered, or this connection is
+    /// the one paused the longest.
+    async fn wait(&mut self) {
+        let buffers = &self.buffers;
+        let ticket = *self.ticket.get_or_insert_with(|| buffers.pause());
+        buffers.wait_below(self.limit, ticket).await;
+    }
+
+    /// Give up the place among the paused connections, once the partial frame
+    /// is read.
+    fn resume(&mut self) {
+        if let Some(ticket) = self.ticket.take() {
+            self.buffers.resume(ticket);
+        }
+    }
+}
+
+impl ReadBuffers {
+    pub fn new() -> ReadBuffers {
+        ReadBuffers::default()
+    }
+
+    /// Returns the number of bytes currently buffered across connections.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Acquire)
+    }
+
+    fn acquire(&self, n: usize) {
+        self.used.fetch_add(n, Ordering::AcqRel);
+    }
+
+    fn release(&self, n: usize) {
+        if n > 0 {
+            self.used.fetch_sub(n, Ordering::AcqRel);
+            self.released.notify_waiters();
+        }
+    }
+
+    /// Register a connection paused with a partial frame, returning its
+    /// ticket.
+    fn pause(&self) -> u64 {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        self.waiting.lock().unwrap().insert(ticket);
+        ticket
+    }
+
+    fn resume(&self, ticket: u64) {
+        self.waiting.lock().unwrap().remove(&ticket);
+        self.released.notify_waiters();
+    }
+
+    /// Wait until fewer than `limit` bytes are buffered, or `ticket` is the
+    /// oldest of the paused connections.
+    async fn wait_below(&self, limit: usize, ticket: u64) {
+        loop {
+            // Register for the notification before checking, so a release
+            // happening in between is not missed.
+            let released = self.released.notified();
+            if self.used() < limit || self.waiting.lock().unwrap().first() == Some(&ticket) {
+                return;
+            }
+            released.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+    use tokio::io::duplex;
+
+    #[tokio::test]
+    async fn read_frame_split_across_single_byte_reads() {
+        let value = Bytes::from(vec![b'x'; 1024]);
+        let frame = Frame::Array(vec![
+            Frame::Bulk(Bytes::from_static(b"set")),
+            Frame::Bulk(Bytes::from_static(b"foo")),
+            Frame::Bulk(value.clone()),
+            Frame::Integer(-42),
+            Frame::Simple("OK".to_string()),
+            Frame::Null,
+        ]);
+
+        let mut encoded =
+            format!("*6\r\n$3\r\nset\r\n$3\r\nfoo\r\n${}\r\n", value.len()).into_bytes();
+        encoded.extend_from_slice(&value);
+        encoded.extend_from_slice(b"\r\n:-42\r\n+OK\r\n$-1\r\n");
+
+        // A one byte pipe forces every `read_buf` call to return a single byte.
+        let (client, server) = duplex(1);
+
+        let writer = tokio::spawn(async move {
+            let mut client = client;
+            for byte in encoded {
+                client.write_all(&[byte]).await.unwrap();
+            }
+        });
+
+        let mut conn = Connection::new(server);
+        assert_eq!(Some(frame), conn.read_frame().await.unwrap());
+
+        writer.await.unwrap();
+        assert_eq!(None, conn.read_frame().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn bulk_containing_crlf_round_trips() {
+        let frame = Frame::Array(vec![
+            Frame::Bulk(Bytes::from_static(b"\r\n")),
+            Frame::Bulk(Bytes::from_static(b"foo\r\nbar\r")),
+            Frame::Bulk(Bytes::from_static(b"\n")),
+        ]);
+
+        let (client, server) = duplex(64);
+        let mut client = Connection::new(client);
+        let mut server = Connection::new(server);
+
+        client.write_frame(&frame).await.unwrap();
+        assert_eq!(Some(frame), server.read_frame().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn nested_arrays_round_trip() {
+        let frame = Frame::Array(vec![Frame::Array(vec![Frame::Integer(1)])]);
+
+        let (client, mut server) = duplex(64);
+        let mut client = Connection::new(client);
+        client.write_frame(&frame).await.unwrap();
+
+        let mut written = [0; 12];
+        server.read_exact(&mut written).await.unwrap();
+        assert_eq!(b"*1\r\n*1\r\n:1\r\n", &written);
+
+        let (mut client, server) = duplex(64);
+        let mut server = Connection::new(server);
+        client.write_all(&written).await.unwrap();
+        assert_eq!(Some(frame), server.read_frame().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn null_frames_round_trip() {
+        for (frame, encoded) in [
+            (Frame::Null, &b"$-1\r\n"[..]),
+            (Frame::NullArray, &b"*-1\r\n"[..]),
+        ] {
+            let (client, mut server) = duplex(64);
+            let mut client = Connection::new(client);
+            client.write_frame(&frame).await.unwrap();
+
+            let mut written = [0; 5];
+            server.read_exact(&mut written).await.unwrap();
+            assert_eq!(encoded, written);
+
+            let (mut client, server) = duplex(64);
+            let mut server = Connection::new(server);
+            client.write_all(encoded).await.unwrap();
+            assert_eq!(Some(frame), server.read_frame().await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn encoded_len_matches_written_bytes() {
+        let frames = [
+            Frame::Simple("OK".to_string()),
+            Frame::Simple(String::new()),
+            Frame::Error("ERR unknown".to_string()),
+            Frame::Integer(0),
+            Frame::Integer(-42),
+            Frame::Integer(i64::MIN),
+            Frame::Integer(i64::MAX),
+            Frame::Bulk(Bytes::new()),
+            Frame::Bulk(Bytes::from(vec![b'x'; 1000])),
+            Frame::Null,
+            Frame::NullArray,
+            Frame::Double(2.5),
+            Frame::Double(-1e300),
+            Frame::Double(f64::NEG_INFINITY),
+            Frame::Double(f64::NAN),
+            Frame::Boolean(true),
+            Frame::BigNumber("-123456789012345678901234567890".to_string()),
+            Frame::Map(vec![
+                (Frame::Simple("a".to_string()), Frame::Integer(1)),
+                (Frame::Integer(2), Frame::Array(vec![Frame::Boolean(false)])),
+            ]),
+            Frame::Array(vec![]),
+            Frame::Push(vec![Frame::Bulk(Bytes::from_static(b"message"))]),
+            Frame::Array(vec![
+                Frame::Bulk(Bytes::from_static(b"a")),
+                Frame::Array(vec![Frame::Integer(10), Frame::Null]),
+                Frame::Array(vec![]),
+            ]),
+        ];
+
+        for protocol in [2, 3] {
+            for frame in &frames {
+                let (client, mut server) = duplex(4096);
+                let mut client = Connection::new(client);
+                client.set_protocol(protocol);
+                client.write_frame(frame).await.unwrap();
+                drop(client);
+
+                let mut written = Vec::new();
+                server.read_to_end(&mut written).await.unwrap();
+                assert_eq!(
+                    written.len(),
+                    frame.encoded_len(protocol),
+                    "RESP{}: {:?}",
+                    protocol,
+                    frame
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn write_to_closed_peer_is_a_disconnect() {
+        let (client, server) = duplex(64);
+        drop(client);
+
+        let mut conn = Connection::new(server);
+        let err: crate::Error = conn
+            .write_frame(&Frame::Simple("OK".to_string()))
+            .await
+            .unwrap_err()
+            .into();
+        assert!(is_disconnect(&err), "{}", err);
+
+        let err: crate::Error = "protocol error; invalid frame format".into();
+        assert!(!is_disconnect(&err));
+    }
+
+    #[tokio::test]
+    async fn recovery_skips_corrupt_frame() {
+        let ping = Frame::Array(vec![Frame::Bulk(Bytes::from_static(b"PING"))]);
+        let input = b"*2\r\n$3\r\nGET\r\n?x\r\n*1\r\n$4\r\nPING\r\n";
+
+        // Disconnects without recovery.
+        let (mut client, server) = duplex(64);
+        let mut conn = Connection::new(server);
+        client.write_all(input).await.unwrap();
+        assert!(conn.read_frame().await.is_err());
+
+        let (mut client, server) = duplex(64);
+        let mut conn = Connection::new(server).with_recovery_limit(64);
+        client.write_all(input).await.unwrap();
+        assert_eq!(Some(ping.clone()), conn.read_frame().await.unwrap());
+
+        // The frame start may only arrive in a later read.
+        client.write_all(b"?garbage\r").await.unwrap();
+        let read = tokio::spawn(async move { conn.read_frame().await.map_err(|e| e.to_string()) });
+        tokio::task::yield_now().await;
+        client.write_all(b"\n*1\r\n$4\r\nPING\r\n").await.unwrap();
+        assert_eq!(Some(ping), read.await.unwrap().unwrap());
+
+        // Gives up past the limit.
+        let (mut client, server) = duplex(256);
+        let mut conn = Connection::new(server).with_recovery_limit(16);
+        client.write_all(&[b'?'; 100]).await.unwrap();
+        client.write_all(b"\r\n*1\r\n$4\r\nPING\r\n").await.unwrap();
+        assert!(conn.read_frame().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn read_buffer_shrinks_after_small_frames() {
+        let (mut client, server) = duplex(64 * 1024);
+        let mut conn = Connection::new(server);
+
+        let value = Bytes::from(vec![b'x'; 1024 * 1024]);
+        let large = Frame::Bulk(value.clone());
+        let writer = tokio::spawn(async move {
+            client.write_all(b"$1048576\r\n").await.unwrap();
+            client.write_all(&value).await.unwrap();
+            client.write_all(b"\r\n").await.unwrap();
+            client
+        });
+        assert_eq!(Some(large), conn.read_frame().await.unwrap());
+        let mut client = writer.await.unwrap();
+
+        let ping = Frame::Simple("PING".to_string());
+        for n in 1..=SHRINK_AFTER_SMALL_FRAMES {
+            // Reading one frame at a time, each read reclaims the space of the
+            // large frame unless the buffer was replaced.
+            assert!(conn.buffer.capacity() > SHRINK_ABOVE, "after {} frames", n);
+            client.write_all(b"+PING\r\n").await.unwrap();
+            assert_eq!(Some(ping.clone()), conn.read_frame().await.unwrap());
+        }
+
+        client.write_all(b"+PING\r\n").await.unwrap();
+        assert_eq!(Some(ping), conn.read_frame().await.unwrap());
+        assert!(conn.buffer.capacity() <= INITIAL_CAPACITY);
+    }
+
+    #[tokio::test]
+    async fn oversized_bulk_rejected_from_header() {
+        let (mut client, server) = duplex(64);
+        let mut conn = Connection::new(server).with_max_bulk_len(1024);
+
+        // The client never sends the body, so only rejecting the header
+        // lets `read_frame` return.
+        client
+            .write_all(b"*2\r\n$3\r\nGET\r\n$1000000000\r\n")
+            .await
+            .unwrap();
+        let err = conn.read_frame().await.unwrap_err().to_string();
+        assert!(err.contains("proto-max-bulk-len"), "{}", err);
+        assert!(conn.buffer.len() < 64);
+    }
+
+    #[tokio::test]
+    async fn peer_addr_of_tcp_connection() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let (socket, addr) = listener.accept().await.unwrap();
+        let conn = Connection::new(socket).with_peer_addr(addr);
+        assert_eq!(client.local_addr().unwrap().to_string(), conn.peer_addr());
+
+        let (_, server) = duplex(64);
+        assert_eq!("unknown", Connection::new(server).peer_addr());
+    }
+
+    #[tokio::test]
+    async fn frame_logging_traces_exchange_only_when_enabled() {
+        use std::sync::Mutex;
+
+        /// Collects everything written by the subscriber.
+        #[derive(Clone, Default)]
+        struct Logs(Arc<Mutex<Vec<u8>>>);
+
+        impl io::Write for Logs {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        async fn exchange(log_frames: bool) -> String {
+            let logs = Logs::default();
+            let writer = logs.clone();
+            let subscriber = tracing_subscriber::fmt()
+                .with_max_level(tracing::Level::TRACE)
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .finish();
+            let _guard = tracing::subscriber::set_default(subscriber);
+
+            let (client, server) = duplex(1024);
+            let mut client = Connection::new(client);
+            let mut server = Connection::new(server).with_frame_logging(log_frames);
+
+            let set = Frame::cmd("set").arg("foo").arg("bar").build();
+            client.write_frame(&set).await.unwrap();
+            server.read_frame().await.unwrap();
+            server
+                .write_frame(&Frame::Simple("OK".to_string()))
+                .await
+                .unwrap();
+
+            let get = Frame::cmd("get").arg("foo").build();
+            client.write_frame(&get).await.unwrap();
+            server.read_frame().await.unwrap();
+            server
+                .write_frame(&Frame::Bulk(Bytes::from("bar")))
+                .await
+                .unwrap();
+
+            let logs = logs.0.lock().unwrap();
+            String::from_utf8(logs.clone()).unwrap()
+        }
+
+        let logs = exchange(true).await;
+        assert!(logs.contains("read set foo bar"), "{}", logs);
+        assert!(logs.contains("write OK"), "{}", logs);
+        assert!(logs.contains("read get foo"), "{}", logs);
+        assert!(logs.contains("write bar"), "{}", logs);
+
+        assert_eq!("", exchange(false).await);
+    }
+
+    #[tokio::test]
+    async fn resp3_frame_is_a_protocol_error() {
+        let (mut client, server) = duplex(64);
+        let mut conn = Connection::new(server);
+
+        client.write_all(b"#t\r\n").await.unwrap();
+
+        let err = conn.read_frame().await.unwrap_err().to_string();
+        assert!(err.contains("RESP3"), "{}", err);
+        assert!(err.contains("'#'"), "{}", err);
+        assert!(err.contains("RESP2 connection"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn resp3_frame_is_read_once_negotiated() {
+        let (mut client, server) = duplex(64);
+        let mut conn = Connection::new(server);
+        conn.set_protocol(3);
+
+        client.write_all(b"#t\r\n~1\r\n+a\r\n").await.unwrap();
+
+        assert_eq!(Some(Frame::Boolean(true)), conn.read_frame().await.unwrap());
+        let err = conn.read_frame().await.unwrap_err().to_string();
+        assert!(err.contains("'~'"), "{}", err);
+        assert!(err.contains("RESP3 connection"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn resp3_frames_round_trip() {
+        let frame = Frame::Array(vec![
+            Frame::Double(2.5),
+            Frame::Double(f64::INFINITY),
+            Frame::Boolean(true),
+            Frame::Boolean(false),
+            Frame::BigNumber("123456789012345678901234567890".to_string()),
+            Frame::Push(vec![Frame::Bulk(Bytes::from_static(b"message"))]),
+            Frame::Map(vec![(
+                Frame::Simple("proto".to_string()),
+                Frame::Map(vec![(Frame::Integer(3), Frame::Double(-0.5))]),
+            )]),
+        ]);
+
+        let (client, server) = duplex(1024);
+        let mut client = Connection::new(client);
+        let mut server = Connection::new(server);
+        client.set_protocol(3);
+        server.set_protocol(3);
+
+        client.write_frame(&frame).await.unwrap();
+        assert_eq!(Some(frame), server.read_frame().await.unwrap());
+
+        let written = |protocol, frame| async move {
+            let (mut client, mut server) = duplex(64);
+            let mut connection = Connection::new(&mut client);
+            connection.set_protocol(protocol);
+            connection.write_frame(&frame).await.unwrap();
+            drop(connection);
+            drop(client);
+            let mut written = Vec::new();
+            server.read_to_end(&mut written).await.unwrap();
+            written
+        };
+
+        let push = Frame::Push(vec![Frame::Integer(1)]);
+        assert!(written(3, push.clone()).await.starts_with(b">"));
+        assert_eq!(b"*1\r\n:1\r\n", &written(2, push).await[..]);
+        assert_eq!(b",2.5\r\n", &written(3, Frame::Double(2.5)).await[..]);
+        assert_eq!(b"_\r\n", &written(3, Frame::Null).await[..]);
+        assert_eq!(b"$3\r\n2.5\r\n", &written(2, Frame::Double(2.5)).await[..]);
+        assert_eq!(b":1\r\n", &written(2, Frame::Boolean(true)).await[..]);
+        assert_eq!(
+            b"*2\r\n:1\r\n:2\r\n",
+            &written(2, Frame::Map(vec![(Frame::Integer(1), Frame::Integer(2))])).await[..]
+        );
+    }
+
+    #[tokio::test]
+    async fn read_budget_pauses_partial_frames() {
+        use std::time::Duration;
+        use tokio::time::timeout;
+
+        let buffers = Arc::new(ReadBuffers::new());
+
+        let (mut large_client, large_server) = duplex(1024);
+        let mut large = Connection::new(large_server).with_read_budget(buffers.clone(), 16);
+
+        // A partial bulk string puts the buffers over the limit.
+        large_client
+            .write_all(b"$64\r\n0123456789abcdef")
+            .await
+            .unwrap();
+        assert!(timeout(Duration::from_millis(50), large.read_frame())
+            .await
+            .is_err());
+        assert!(buffers.used() >= 16);
+
+        // A later partial frame is not read further while over the limit.
+        let (mut paused_client, paused_server) = duplex(1024);
+        let mut paused = Connection::new(paused_server).with_read_budget(buffers.clone(), 16);
+        paused_client.write_all(b"$8\r\n0123").await.unwrap();
+        assert!(timeout(Duration::from_millis(50), paused.read_frame())
+            .await
+            .is_err());
+        let used = buffers.used();
+        paused_client.write_all(b"4567\r\n").await.unwrap();
+        assert!(timeout(Duration::from_millis(50), paused.read_frame())
+            .await
+            .is_err());
+        assert_eq!(used, buffers.used());
+
+        // Connections without a pending frame are still served.
+        let (mut small_client, small_server) = duplex(1024);
+        let mut small = Connection::new(small_server).with_read_budget(buffers.clone(), 16);
+        small_client.write_all(b"+PING\r\n").await.unwrap();
+        assert_eq!(
+            Some(Frame::Simple("PING".to_string())),
+            small.read_frame().await.unwrap()
+        );
+        assert_eq!(used, buffers.used());
+
+        // The oldest partial frame is read although it alone exceeds the
+        // limit, after which the paused one is.
+        large_client.write_all(&[b'x'; 48]).await.unwrap();
+        large_client.write_all(b"\r\n").await.unwrap();
+        let frame = timeout(Duration::from_secs(1), large.read_frame()).await;
+        assert!(matches!(
+            frame.unwrap().unwrap(),
+            Some(Frame::Bulk(data)) if data.len() == 64
+        ));
+        let frame = timeout(Duration::from_secs(1), paused.read_frame()).await;
+        assert_eq!(
+            Some(Frame::Bulk(Bytes::from_static(b"01234567"))),
+            frame.unwrap().unwrap()
+        );
+
+        drop(large);
+        drop(paused);
+        assert_eq!(0, buffers.used());
+    }
+
+    #[tokio::test]
+    async fn read_budget_partial_frames_adding_up_to_limit() {
+        use std::time::Duration;
+        use tokio::time::timeout;
+
+        let buffers = Arc::new(ReadBuffers::new());
+
+        let (mut a_client, a_server) = duplex(1024);
+        let mut a = Connection::new(a_server).with_read_budget(buffers.clone(), 16);
+        let (mut b_client, b_server) = duplex(1024);
+        let mut b = Connection::new(b_server).with_read_budget(buffers.clone(), 16);
+
+        // Each connection holds half of the limit.
+        a_client.write_all(b"$10\r\n012").await.unwrap();
+        b_client.write_all(b"$10\r\n345").await.unwrap();
+        for conn in [&mut a, &mut b] {
+            assert!(timeout(Duration::from_millis(50), conn.read_frame())
+                .await
+                .is_err());
+        }
+        assert_eq!(16, buffers.used());
+
+        a_client.write_all(b"3456789\r\n").await.unwrap();
+        b_client.write_all(b"6789012\r\n").await.unwrap();
+        let (a, b) = timeout(Duration::from_secs(1), async {
+            tokio::join!(a.read_frame(), b.read_frame())
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            Some(Frame::Bulk(Bytes::from_static(b"0123456789"))),
+            a.unwrap()
+        );
+        assert_eq!(
+            Some(Frame::Bulk(Bytes::from_static(b"3456789012"))),
+            b.unwrap()
+        );
+        assert_eq!(0, buffers.used());
+    }
+}
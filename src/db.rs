@@ -1,10 +1,27 @@
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use std::collections::{BTreeSet, HashMap};
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::{broadcast, Notify};
 use tokio::time::Instant;
 
+use crate::cmd::CommandError;
+use crate::config::{ActiveExpire, Settings};
+use crate::stream::{Entries, Fields, NewId, Stream, StreamId};
+
+/// Values `0` up to this bound, exclusive, are stored as shared integers.
+const SHARED_INTEGERS: usize = 10_000;
+
+/// Interval between two cycles of the sampling expiration strategy.
+const ACTIVE_EXPIRE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of random keys with an expiration checked per lock acquisition
+/// when sampling.
+const ACTIVE_EXPIRE_SAMPLE: usize = 20;
+
+/// Time a single sampling cycle may spend removing keys.
+const ACTIVE_EXPIRE_CYCLE_BUDGET: Duration = Duration::from_millis(25);
+
 pub struct DbDropGuard {
     db: Db,
 }
@@ -17,19 +34,78 @@ pub struct Db {
 pub struct Shared {
     state: Mutex<State>,
     background_task: Notify,
+    /// Source of the current time for expirations.
+    clock: Arc<dyn Clock>,
+    /// ID of this node, as reported by `CLUSTER MYID`.
+    node_id: String,
 }
 
 pub struct State {
     entries: HashMap<String, Entry>,
     expirations: BTreeSet<(Instant, String)>,
+    /// The keys in `expirations`, for the sampling strategy to pick from.
+    volatile: Volatile,
     /// Map from channel name to sender.
     pub_sub: HashMap<String, broadcast::Sender<Bytes>>,
+    /// Map from stream key to the notifier of clients blocked in `XREAD`.
+    stream_waiters: HashMap<String, Arc<Notify>>,
+    /// The master declared by `REPLICAOF`, or `None` when acting as a master.
+    master: Option<(String, u16)>,
+    settings: Settings,
     shutdown: bool,
 }
 
 struct Entry {
-    data: Bytes,
+    data: Value,
     expires_at: Option<Instant>,
+    /// Number of accesses while `hotkeys-tracking` is enabled.
+    hits: u64,
+}
+
+/// Source of the current time used for expirations.
+///
+/// Tests can provide their own to control time directly, without pausing the
+/// Tokio clock.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The Tokio clock.
+struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A value stored under a key.
+enum Value {
+    String(Bytes),
+    Stream(Stream),
+}
+
+/// A description of the value held by a key, for debugging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyInfo {
+    /// The type of the value, as Redis names it.
+    pub kind: &'static str,
+    /// How the value is represented, as Redis names it.
+    pub encoding: &'static str,
+    /// Length of a string, or number of entries of a stream.
+    pub len: usize,
+    /// Time left before the key expires, if it has an expiration.
+    pub ttl: Option<Duration>,
+}
+
+/// Condition on the existence of a key for `Db::set_if` to store a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetIf {
+    Always,
+    /// Only if the key does not exist, for `SET NX`.
+    Absent,
+    /// Only if the key exists, for `SET XX`.
+    Present,
 }
 
 impl DbDropGuard {
@@ -42,6 +118,12 @@ impl DbDropGuard {
     }
 }
 
+impl Default for DbDropGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Drop for DbDropGuard {
     fn drop(&mut self) {
         self.db.shutdown_clean_task();
@@ -50,49 +132,372 @@ impl Drop for DbDropGuard {
 
 impl Db {
     pub fn new() -> Db {
-        let shared = Arc::new(Shared::new());
+        Db::with_clock(Arc::new(TokioClock))
+    }
+
+    /// Create a database reading the current time from `clock`.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Db {
+        let shared = Arc::new(Shared::with_clock(clock));
 
         tokio::spawn(clean_expired_tasks(shared.clone()));
 
         Db { shared }
     }
 
-    pub fn get(&self, key: &str) -> Option<Bytes> {
-        let state = self.shared.state.lock().unwrap();
-        state.entries.get(key).map(|entry| entry.data.clone())
+    /// Returns the string held by `key`. Fails if `key` holds another type
+    /// of value.
+    ///
+    /// A key past its deadline is removed on the spot rather than left for
+    /// the background task.
+    pub fn get(&self, key: &str) -> crate::Result<Option<Bytes>> {
+        let mut state = self.shared.state.lock().unwrap();
+        let tracking = state.settings.hotkeys_tracking;
+
+        self.remove_expired(&mut state, key);
+        let entry = match state.entries.get_mut(key) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        if tracking {
+            entry.hits += 1;
+        }
+
+        match &entry.data {
+            Value::String(data) => Ok(Some(data.clone())),
+            _ => Err(CommandError::WrongType.into()),
+        }
+    }
+
+    /// Remove `key` and returns the string it held. Fails without removing
+    /// anything if `key` holds another type of value.
+    pub fn get_del(&self, key: &str) -> crate::Result<Option<Bytes>> {
+        let mut state = self.shared.state.lock().unwrap();
+        let now = self.shared.clock.now();
+
+        let entry = match state.entries.get(key) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let expired = entry.expires_at.is_some_and(|when| when <= now);
+        if !expired && !matches!(entry.data, Value::String(_)) {
+            return Err(CommandError::WrongType.into());
+        }
+
+        let entry = state.entries.remove(key).unwrap();
+        if let Some(when) = entry.expires_at {
+            state.remove_expiration(when, key);
+        }
+
+        match entry.data {
+            Value::String(data) if !expired => Ok(Some(data)),
+            _ => Ok(None),
+        }
     }
 
-    pub fn set(&self, key: String, value: Bytes, expire: Option<Duration>) {
-        let expires_at = expire.map(|duration| Instant::now() + duration);
+    /// Returns the string held by each of `keys`, in order, under a single
+    /// lock acquisition. Missing keys, keys past their deadline and keys
+    /// holding another type of value give `None`.
+    pub fn mget(&self, keys: &[String]) -> Vec<Option<Bytes>> {
+        let mut state = self.shared.state.lock().unwrap();
+        let now = self.shared.clock.now();
+        let tracking = state.settings.hotkeys_tracking;
+
+        keys.iter()
+            .map(|key| {
+                let entry = state.entries.get_mut(key)?;
+                if entry.expires_at.is_some_and(|when| when <= now) {
+                    return None;
+                }
+                if tracking {
+                    entry.hits += 1;
+                }
+
+                match &entry.data {
+                    Value::String(data) => Some(data.clone()),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Store `value` at `key`, expiring after `expire`. Fails if the deadline
+    /// is too far to be represented.
+    pub fn set(&self, key: String, value: Bytes, expire: Option<Duration>) -> crate::Result<()> {
+        let expires_at = deadline(self.shared.clock.now(), expire)?;
+
+        let mut state = self.shared.state.lock().unwrap();
+        self.insert_string(&mut state, key, value, expires_at);
+        Ok(())
+    }
+
+    /// Store each value at its key, without expiration, under a single lock
+    /// acquisition. A key given more than once is left with its last value.
+    pub fn mset(&self, pairs: Vec<(String, Bytes)>) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        for (key, value) in pairs {
+            self.insert_string(&mut state, key, value, None);
+        }
+    }
 
+    /// Store `value` at `key` if `condition` holds, expiring after `expire`.
+    /// Returns whether the value was stored, and the string `key` held before
+    /// if `get` is set.
+    ///
+    /// With `get`, fails without storing anything if `key` holds another type
+    /// of value. Also fails if the deadline is too far to be represented.
+    pub fn set_if(
+        &self,
+        key: String,
+        value: Bytes,
+        expire: Option<Duration>,
+        condition: SetIf,
+        get: bool,
+    ) -> crate::Result<(bool, Option<Bytes>)> {
+        let now = self.shared.clock.now();
+        let expires_at = deadline(now, expire)?;
         let mut state = self.shared.state.lock().unwrap();
 
+        let old = state
+            .entries
+            .get(&key)
+            .filter(|entry| entry.expires_at.is_none_or(|when| when > now));
+
+        let old_value = match old.map(|entry| &entry.data) {
+            Some(Value::String(data)) if get => Some(data.clone()),
+            Some(_) if get => return Err(CommandError::WrongType.into()),
+            _ => None,
+        };
+
+        let store = match condition {
+            SetIf::Always => true,
+            SetIf::Absent => old.is_none(),
+            SetIf::Present => old.is_some(),
+        };
+        if store {
+            self.insert_string(&mut state, key, value, expires_at);
+        }
+
+        Ok((store, old_value))
+    }
+
+    /// Store `value` at `key`, without expiration, only if `key` does not
+    /// exist. Returns whether the value was stored.
+    pub fn set_nx(&self, key: String, value: Bytes) -> bool {
+        // Without `get`, `set_if` cannot fail.
+        self.set_if(key, value, None, SetIf::Absent, false)
+            .is_ok_and(|(stored, _)| stored)
+    }
+
+    /// Returns the string held by `key`, or stores and returns the one
+    /// computed by `f` if `key` does not exist, expiring after `expire`.
+    ///
+    /// The state lock is held while `f` runs, so that concurrent callers
+    /// compute the value at most once. `f` therefore blocks every other
+    /// command: it must be fast, and must not use the database, which would
+    /// deadlock. Fails if `key` holds another type of value, or if the
+    /// deadline is too far to be represented.
+    pub fn get_or_insert_with(
+        &self,
+        key: String,
+        expire: Option<Duration>,
+        f: impl FnOnce() -> Bytes,
+    ) -> crate::Result<Bytes> {
+        let now = self.shared.clock.now();
+        let expires_at = deadline(now, expire)?;
+        let mut state = self.shared.state.lock().unwrap();
+        let tracking = state.settings.hotkeys_tracking;
+
+        if let Some(entry) = state.entries.get_mut(&key) {
+            // An entry past its deadline is replaced as if it were gone.
+            if entry.expires_at.is_none_or(|when| when > now) {
+                if tracking {
+                    entry.hits += 1;
+                }
+                return match &entry.data {
+                    Value::String(data) => Ok(data.clone()),
+                    _ => Err(CommandError::WrongType.into()),
+                };
+            }
+        }
+
+        let value = f();
+        self.insert_string(&mut state, key, value.clone(), expires_at);
+
+        Ok(value)
+    }
+
+    /// Append `value` to the string held by `key`, storing `value` as is if
+    /// `key` does not exist, and returns the new length. An existing
+    /// expiration is kept. Fails if `key` holds another type of value.
+    pub fn append(&self, key: &str, value: Bytes) -> crate::Result<usize> {
+        let mut state = self.shared.state.lock().unwrap();
+        let now = self.shared.clock.now();
+        let tracking = state.settings.hotkeys_tracking;
+
+        if let Some(entry) = state.entries.get_mut(key) {
+            if entry.expires_at.is_none_or(|when| when > now) {
+                let data = match &entry.data {
+                    Value::String(data) => data,
+                    _ => return Err(CommandError::WrongType.into()),
+                };
+
+                let mut appended = BytesMut::with_capacity(data.len() + value.len());
+                appended.extend_from_slice(data);
+                appended.extend_from_slice(&value);
+                let appended = appended.freeze();
+                let len = appended.len();
+
+                entry.data = Value::String(shared_integer(&appended).unwrap_or(appended));
+                if tracking {
+                    entry.hits += 1;
+                }
+                return Ok(len);
+            }
+        }
+
+        let len = value.len();
+        self.insert_string(&mut state, key.to_string(), value, None);
+
+        Ok(len)
+    }
+
+    /// Add `delta` to the integer held by `key` as a decimal string, starting
+    /// from `0` if `key` does not exist, and returns the result. An existing
+    /// expiration is kept. Fails if the value is not an integer or the result
+    /// overflows, or if `key` holds another type of value.
+    pub fn incr_by(&self, key: &str, delta: i64) -> crate::Result<i64> {
+        let mut state = self.shared.state.lock().unwrap();
+        let now = self.shared.clock.now();
+        let tracking = state.settings.hotkeys_tracking;
+
+        if let Some(entry) = state.entries.get_mut(key) {
+            if entry.expires_at.is_none_or(|when| when > now) {
+                let current = match &entry.data {
+                    Value::String(data) => std::str::from_utf8(data)
+                        .ok()
+                        .and_then(|s| s.parse::<i64>().ok()),
+                    _ => return Err(CommandError::WrongType.into()),
+                };
+
+                let value = match current.and_then(|n| n.checked_add(delta)) {
+                    Some(value) => value,
+                    None => return Err(not_an_integer()),
+                };
+
+                let data = Bytes::from(value.to_string());
+                entry.data = Value::String(shared_integer(&data).unwrap_or(data));
+                if tracking {
+                    entry.hits += 1;
+                }
+                return Ok(value);
+            }
+        }
+
+        self.insert_string(
+            &mut state,
+            key.to_string(),
+            Bytes::from(delta.to_string()),
+            None,
+        );
+
+        Ok(delta)
+    }
+
+    /// Store `value` at `key` in `state`, replacing any value it held.
+    fn insert_string(
+        &self,
+        state: &mut State,
+        key: String,
+        value: Bytes,
+        expires_at: Option<Instant>,
+    ) {
+        let hits = if state.settings.hotkeys_tracking {
+            state.entries.get(&key).map_or(0, |old| old.hits) + 1
+        } else {
+            0
+        };
+
         let entry = Entry {
-            data: value,
+            data: Value::String(shared_integer(&value).unwrap_or(value)),
             expires_at,
+            hits,
         };
 
         let old = state.entries.insert(key.clone(), entry);
+        let old_expires_at = old.and_then(|old| old.expires_at);
+
+        self.update_expiration(state, key, old_expires_at, expires_at);
+    }
 
+    /// Move the expiration record of `key` from `old` to `new`, waking the
+    /// background task if `new` is the earliest expiration.
+    fn update_expiration(
+        &self,
+        state: &mut State,
+        key: String,
+        old: Option<Instant>,
+        new: Option<Instant>,
+    ) {
         if let Some(old) = old {
-            if let Some(expires_at) = old.expires_at {
-                state.expirations.remove(&(expires_at, key.clone()));
-            }
+            state.remove_expiration(old, &key);
         }
 
-        if let Some(expires_at) = expires_at {
+        if let Some(new) = new {
             if let Some(&(earliest, _)) = state.expirations.first() {
-                if expires_at < earliest {
-                    self.shared.background_task.notify_waiters();
+                if new < earliest {
+                    self.wake_background();
                 }
             } else {
-                self.shared.background_task.notify_waiters();
+                self.wake_background();
             }
 
-            state.expirations.insert((expires_at, key));
+            state.insert_expiration(new, key);
         }
     }
 
+    /// Remove `key` from `state` if it is past its deadline, rather than
+    /// leaving it for the background task.
+    fn remove_expired(&self, state: &mut State, key: &str) {
+        let when = match state.entries.get(key) {
+            Some(Entry {
+                expires_at: Some(when),
+                ..
+            }) => *when,
+            _ => return,
+        };
+
+        if when <= self.shared.clock.now() {
+            state.entries.remove(key);
+            state.remove_expiration(when, key);
+        }
+    }
+
+    /// Set `key` to expire after `duration`, replacing any expiration it had.
+    /// Returns `false` if `key` does not exist.
+    pub fn expire(&self, key: &str, duration: Duration) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        let now = self.shared.clock.now();
+
+        let entry = match state.entries.get_mut(key) {
+            Some(entry) if entry.expires_at.is_none_or(|when| when > now) => entry,
+            _ => return false,
+        };
+
+        // A deadline too far to be represented never comes.
+        let expires_at = now.checked_add(duration);
+        let old = std::mem::replace(&mut entry.expires_at, expires_at);
+
+        self.update_expiration(&mut state, key.to_string(), old, expires_at);
+        true
+    }
+
+    /// Wake the background task so that it recomputes when the next key
+    /// expires. Needed after changing expirations without going through `set`.
+    pub fn wake_background(&self) {
+        self.shared.background_task.notify_waiters();
+    }
+
     pub fn shutdown_clean_task(&self) {
         let mut state = self.shared.state.lock().unwrap();
         state.shutdown = true;
@@ -100,38 +505,418 @@ impl Db {
         self.shared.background_task.notify_waiters();
     }
 
-    pub fn subscribe(&self, channel: &str) -> broadcast::Receiver<Bytes> {
+    /// Subscribe to `channel`. Fails if the channel does not exist yet and
+    /// `max-pubsub-channels` channels already do.
+    pub fn subscribe(&self, channel: &str) -> crate::Result<broadcast::Receiver<Bytes>> {
         let mut state = self.shared.state.lock().unwrap();
 
         match state.pub_sub.get(channel) {
-            Some(tx) => tx.subscribe(),
+            Some(tx) => Ok(tx.subscribe()),
             None => {
+                let max = state.settings.max_pubsub_channels;
+                if max > 0 && state.pub_sub.len() >= max {
+                    return Err("max number of pub/sub channels reached".into());
+                }
+
                 let (tx, rx) = broadcast::channel(1024);
                 state.pub_sub.insert(channel.to_string(), tx);
-                rx
+                Ok(rx)
+            }
+        }
+    }
+
+    /// Release `channel` once its last subscriber has gone, so that channels
+    /// nobody listens to anymore do not hold on to a sender.
+    pub fn unsubscribe(&self, channel: &str) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        if let Some(tx) = state.pub_sub.get(channel) {
+            // Subscribing takes the same lock, so no receiver can show up
+            // before the channel is removed.
+            if tx.receiver_count() == 0 {
+                state.pub_sub.remove(channel);
             }
         }
     }
 
+    /// Forcibly remove `channel`. Dropping its sender closes the channel:
+    /// current subscribers receive the messages already buffered, then see
+    /// the channel end.
+    pub fn clear_channel(&self, channel: &str) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.pub_sub.remove(channel);
+    }
+
     /// Publish a message to the channel. Returns the number of subscribers
     /// listening on the channel.
+    ///
+    /// A channel left without any live subscriber is removed.
     pub fn publish(&self, channel: String, message: Bytes) -> usize {
-        let state = self.shared.state.lock().unwrap();
+        let mut state = self.shared.state.lock().unwrap();
 
         let tx = match state.pub_sub.get(&channel) {
             Some(tx) => tx,
             None => return 0,
         };
 
-        tx.send(message).unwrap_or(0)
+        match tx.send(message) {
+            Ok(num_subscribers) => num_subscribers,
+            Err(_) => {
+                // Every receiver has been dropped. Subscribing takes the same
+                // lock, so none can show up before the channel is removed.
+                state.pub_sub.remove(&channel);
+                0
+            }
+        }
+    }
+
+    /// Returns up to `n` keys with the most accesses recorded while
+    /// `hotkeys-tracking` was enabled, most accessed first.
+    pub fn hotkeys(&self, n: usize) -> Vec<(String, u64)> {
+        let state = self.shared.state.lock().unwrap();
+
+        let mut hot: Vec<_> = state
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.hits > 0)
+            .map(|(key, entry)| (key.clone(), entry.hits))
+            .collect();
+
+        hot.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hot.truncate(n);
+        hot
+    }
+
+    /// Returns up to `n` keys with an expiration and the time left before
+    /// they expire, soonest first.
+    pub fn expirations(&self, n: usize) -> Vec<(String, Duration)> {
+        let state = self.shared.state.lock().unwrap();
+
+        let now = self.shared.clock.now();
+        state
+            .expirations
+            .iter()
+            .take(n)
+            .map(|(when, key)| (key.clone(), when.saturating_duration_since(now)))
+            .collect()
+    }
+
+    /// Remove the expiration of `key`. Returns `false` if `key` does not exist
+    /// or has no expiration.
+    pub fn persist(&self, key: &str) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        let now = self.shared.clock.now();
+
+        let entry = match state.entries.get_mut(key) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        match entry.expires_at {
+            Some(when) if when > now => {
+                entry.expires_at = None;
+                state.remove_expiration(when, key);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns whether `key` exists. A key past its deadline does not, even if
+    /// the background task has not removed it yet.
+    pub fn exists(&self, key: &str) -> bool {
+        let state = self.shared.state.lock().unwrap();
+
+        match state.entries.get(key) {
+            Some(entry) => match entry.expires_at {
+                Some(when) => when > self.shared.clock.now(),
+                None => true,
+            },
+            None => false,
+        }
+    }
+
+    /// Returns the wall-clock time at which `key` expires, `Some(None)` if it
+    /// has no expiration, or `None` if it does not exist.
+    pub fn expire_time(&self, key: &str) -> Option<Option<SystemTime>> {
+        let state = self.shared.state.lock().unwrap();
+
+        let entry = state.entries.get(key)?;
+        let now = self.shared.clock.now();
+        match entry.expires_at {
+            Some(when) if when <= now => None,
+            Some(when) => Some(Some(to_system_time(when, now))),
+            None => Some(None),
+        }
+    }
+
+    /// Describe the value held by `key`, or returns `None` if it does not
+    /// exist.
+    pub fn describe(&self, key: &str) -> Option<KeyInfo> {
+        let state = self.shared.state.lock().unwrap();
+
+        let entry = state.entries.get(key)?;
+        let ttl = match entry.expires_at {
+            Some(when) => match when.checked_duration_since(self.shared.clock.now()) {
+                Some(ttl) if !ttl.is_zero() => Some(ttl),
+                _ => return None,
+            },
+            None => None,
+        };
+
+        let info = match &entry.data {
+            Value::String(data) => KeyInfo {
+                kind: "string",
+                encoding: string_encoding(data),
+                len: data.len(),
+                ttl,
+            },
+            Value::Stream(stream) => KeyInfo {
+                kind: "stream",
+                encoding: "stream",
+                len: stream.len(),
+                ttl,
+            },
+        };
+        Some(info)
+    }
+
+    /// Append an entry to the stream held by `key`, creating the stream if
+    /// needed. Returns the ID of the new entry.
+    ///
+    /// A stream past its deadline is replaced by a new one, without
+    /// expiration.
+    pub fn xadd(&self, key: String, id: NewId, fields: Fields) -> crate::Result<StreamId> {
+        let mut state = self.shared.state.lock().unwrap();
+        let tracking = state.settings.hotkeys_tracking;
+
+        self.remove_expired(&mut state, &key);
+
+        let id = match state.entries.get_mut(&key) {
+            Some(entry) => {
+                let stream = match &mut entry.data {
+                    Value::Stream(stream) => stream,
+                    _ => return Err(CommandError::WrongType.into()),
+                };
+
+                let id = stream.add(id, fields)?;
+                if tracking {
+                    entry.hits += 1;
+                }
+                id
+            }
+            None => {
+                // The key is only created once the entry is known to be valid.
+                let mut stream = Stream::new();
+                let id = stream.add(id, fields)?;
+
+                let entry = Entry {
+                    data: Value::Stream(stream),
+                    expires_at: None,
+                    hits: tracking as u64,
+                };
+                state.entries.insert(key.clone(), entry);
+                id
+            }
+        };
+
+        if let Some(notify) = state.stream_waiters.get(&key) {
+            notify.notify_waiters();
+        }
+
+        Ok(id)
+    }
+
+    /// Returns the entries added after the given ID of each stream, up to
+    /// `count` per stream. Streams without such entries are left out.
+    pub fn xread(
+        &self,
+        streams: &[(String, StreamId)],
+        count: Option<usize>,
+    ) -> crate::Result<Vec<(String, Entries)>> {
+        let mut read = Vec::new();
+        for (key, id) in streams {
+            let entries = self.read_stream(key, |stream| stream.range_after(*id, count))?;
+            match entries {
+                Some(entries) if !entries.is_empty() => read.push((key.clone(), entries)),
+                _ => {}
+            }
+        }
+
+        Ok(read)
+    }
+
+    /// Returns the ID of the last entry added to the stream held by `key`,
+    /// or `0-0` if there is no such stream.
+    pub fn xlast_id(&self, key: &str) -> crate::Result<StreamId> {
+        let id = self.read_stream(key, |stream| stream.last_id())?;
+        Ok(id.unwrap_or(StreamId::MIN))
+    }
+
+    /// Returns the notifier woken whenever an entry is added to the stream
+    /// held by `key`.
+    pub fn stream_notify(&self, key: &str) -> Arc<Notify> {
+        let mut state = self.shared.state.lock().unwrap();
+        state
+            .stream_waiters
+            .entry(key.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Release the notifier of `key` once no client waits on it anymore.
+    /// Callers drop their handle first.
+    pub fn release_stream_notify(&self, key: &str) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        if let Some(notify) = state.stream_waiters.get(key) {
+            // Handing out a notifier takes the same lock, so no handle can
+            // show up before it is removed.
+            if Arc::strong_count(notify) == 1 {
+                state.stream_waiters.remove(key);
+            }
+        }
+    }
+
+    /// Returns the number of entries in the stream held by `key`.
+    pub fn xlen(&self, key: &str) -> crate::Result<usize> {
+        let len = self.read_stream(key, |stream| stream.len())?;
+        Ok(len.unwrap_or(0))
+    }
+
+    /// Returns the entries of the stream held by `key` with IDs between
+    /// `start` and `end` inclusive, up to `count` of them.
+    pub fn xrange(
+        &self,
+        key: &str,
+        start: StreamId,
+        end: StreamId,
+        count: Option<usize>,
+    ) -> crate::Result<Entries> {
+        let entries = self.read_stream(key, |stream| stream.range(start, end, count))?;
+        Ok(entries.unwrap_or_default())
+    }
+
+    /// Apply `f` to the stream held by `key`, if any. A stream past its
+    /// deadline is removed instead.
+    fn read_stream<T>(&self, key: &str, f: impl FnOnce(&Stream) -> T) -> crate::Result<Option<T>> {
+        let mut state = self.shared.state.lock().unwrap();
+        let tracking = state.settings.hotkeys_tracking;
+
+        self.remove_expired(&mut state, key);
+
+        let entry = match state.entries.get_mut(key) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        if tracking {
+            entry.hits += 1;
+        }
+
+        match &entry.data {
+            Value::Stream(stream) => Ok(Some(f(stream))),
+            _ => Err(CommandError::WrongType.into()),
+        }
+    }
+
+    /// Returns every live string key with its value and remaining time to
+    /// live, as of a single point in time. Keys that have expired but not yet
+    /// been removed are left out, as are keys holding other types of values.
+    pub fn snapshot(&self) -> Vec<(String, Bytes, Option<Duration>)> {
+        let state = self.shared.state.lock().unwrap();
+
+        let now = self.shared.clock.now();
+        state
+            .entries
+            .iter()
+            .filter_map(|(key, entry)| {
+                let data = match &entry.data {
+                    Value::String(data) => data.clone(),
+                    _ => return None,
+                };
+
+                let ttl = match entry.expires_at {
+                    Some(when) if when <= now => return None,
+                    Some(when) => Some(when - now),
+                    None => None,
+                };
+
+                Some((key.clone(), data, ttl))
+            })
+            .collect()
+    }
+
+    /// Iterate over every live string key and its value, for embedding
+    /// mini-redis as a library.
+    ///
+    /// The iterator walks a [`snapshot`](Db::snapshot): the lock is released
+    /// before iterating, and changes made meanwhile are not seen.
+    pub fn iter(&self) -> impl Iterator<Item = (String, Bytes)> {
+        self.snapshot()
+            .into_iter()
+            .map(|(key, value, _)| (key, value))
+    }
+
+    /// Returns a copy of the current server settings.
+    pub fn settings(&self) -> Settings {
+        let state = self.shared.state.lock().unwrap();
+        state.settings.clone()
+    }
+
+    /// Change the server settings with `f`. The settings are left untouched if
+    /// `f` fails.
+    pub fn update_settings<T>(
+        &self,
+        f: impl FnOnce(&mut Settings) -> crate::Result<T>,
+    ) -> crate::Result<T> {
+        let mut state = self.shared.state.lock().unwrap();
+
+        let mut settings = state.settings.clone();
+        let ret = f(&mut settings)?;
+        state.settings = settings;
+
+        // Let the background task pick up a new expiration strategy.
+        self.wake_background();
+
+        Ok(ret)
+    }
+
+    /// Declare the master this instance replicates from. `None` turns the
+    /// instance back into a master.
+    pub fn set_master(&self, master: Option<(String, u16)>) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.master = master;
+    }
+
+    /// Returns the ID of this node, fixed for the lifetime of the database.
+    pub fn node_id(&self) -> &str {
+        &self.shared.node_id
+    }
+
+    /// Returns the master declared by `REPLICAOF`, if any.
+    pub fn master(&self) -> Option<(String, u16)> {
+        let state = self.shared.state.lock().unwrap();
+        state.master.clone()
+    }
+}
+
+impl Default for Db {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Shared {
     pub fn new() -> Shared {
+        Shared::with_clock(Arc::new(TokioClock))
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Shared {
         Shared {
             state: Mutex::new(State::new()),
             background_task: Notify::new(),
+            clock,
+            node_id: generate_node_id(),
         }
     }
 
@@ -149,19 +934,75 @@ impl Shared {
             return None;
         }
 
-        let now = Instant::now();
+        let now = self.clock.now();
 
         while let Some(&(expiration, ref key)) = state.expirations.iter().next() {
             if expiration > now {
                 return Some(expiration);
             }
 
-            state.entries.remove(key);
-            state.expirations.remove(&(expiration, key.clone()));
+            let key = key.clone();
+            state.entries.remove(&key);
+            state.remove_expiration(expiration, &key);
         }
 
         None
     }
+
+    fn active_expire(&self) -> ActiveExpire {
+        let state = self.state.lock().unwrap();
+        state.settings.active_expire
+    }
+
+    /// Check `ACTIVE_EXPIRE_SAMPLE` random keys with an expiration and remove
+    /// the expired ones. Returns `true` if more than a quarter of them were
+    /// expired, meaning many more are likely due.
+    fn sample_expired(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+
+        let now = self.clock.now();
+        let samples = ACTIVE_EXPIRE_SAMPLE.min(state.volatile.len());
+        let mut expired = 0;
+
+        for _ in 0..samples {
+            let key = match state.volatile.random() {
+                Some(key) => key.to_string(),
+                None => break,
+            };
+            let when = match state.entries.get(&key) {
+                Some(Entry {
+                    expires_at: Some(when),
+                    ..
+                }) => *when,
+                _ => continue,
+            };
+
+            if when <= now {
+                state.entries.remove(&key);
+                state.remove_expiration(when, &key);
+                expired += 1;
+            }
+        }
+
+        expired * 4 > samples
+    }
+
+    /// Run one cycle of the sampling strategy, releasing the lock between
+    /// samples so other commands can make progress.
+    async fn sample_expired_cycle(&self) {
+        let deadline = Instant::now() + ACTIVE_EXPIRE_CYCLE_BUDGET;
+
+        while self.sample_expired() && Instant::now() < deadline {
+            tokio::task::yield_now().await;
+        }
+    }
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl State {
@@ -169,14 +1010,130 @@ impl State {
         State {
             entries: HashMap::new(),
             expirations: BTreeSet::new(),
+            volatile: Volatile::default(),
             pub_sub: HashMap::new(),
+            stream_waiters: HashMap::new(),
+            master: None,
+            settings: Settings::new(),
             shutdown: false,
         }
     }
 }
 
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl State {
+    /// Record that `key` expires at `when`.
+    fn insert_expiration(&mut self, when: Instant, key: String) {
+        self.volatile.insert(key.clone());
+        self.expirations.insert((when, key));
+    }
+
+    /// Forget that `key` expires at `when`.
+    fn remove_expiration(&mut self, when: Instant, key: &str) {
+        self.volatile.remove(key);
+        self.expirations.remove(&(when, key.to_string()));
+    }
+}
+
+/// A set of keys from which one can be picked at random in constant time.
+#[derive(Default)]
+struct Volatile {
+    keys: Vec<String>,
+    /// Map from key to its index in `keys`.
+    slots: HashMap<String, usize>,
+}
+
+impl Volatile {
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn insert(&mut self, key: String) {
+        if !self.slots.contains_key(&key) {
+            self.slots.insert(key.clone(), self.keys.len());
+            self.keys.push(key);
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(slot) = self.slots.remove(key) {
+            self.keys.swap_remove(slot);
+            if let Some(moved) = self.keys.get(slot) {
+                self.slots.insert(moved.clone(), slot);
+            }
+        }
+    }
+
+    /// Returns one of the keys, picked at random, or `None` if there are none.
+    fn random(&self) -> Option<&str> {
+        use std::hash::{BuildHasher, RandomState};
+
+        if self.keys.is_empty() {
+            return None;
+        }
+
+        // Each `RandomState` is seeded differently, see `generate_node_id`.
+        let n = RandomState::new().hash_one(self.keys.len());
+        Some(&self.keys[(n % self.keys.len() as u64) as usize])
+    }
+}
+
+/// Returns the instant `expire` after `now`. As in Redis, fails if the
+/// deadline in milliseconds since the Unix epoch does not fit an `i64`, which
+/// keeps it representable both as an `Instant` and as a `SystemTime`.
+fn deadline(now: Instant, expire: Option<Duration>) -> crate::Result<Option<Instant>> {
+    let duration = match expire {
+        Some(duration) => duration,
+        None => return Ok(None),
+    };
+
+    let unix_now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO);
+    let when = match unix_now.as_millis().checked_add(duration.as_millis()) {
+        Some(ms) if ms <= i64::MAX as u128 => now.checked_add(duration),
+        _ => None,
+    };
+
+    match when {
+        Some(when) => Ok(Some(when)),
+        None => Err(CommandError::Err("invalid expire time in 'set' command".to_string()).into()),
+    }
+}
+
+/// Convert `when` to wall-clock time, `now` being the current instant.
+///
+/// `Instant` is monotonic and has no relation to the Unix epoch, so the
+/// conversion goes through the offset between both clocks sampled back to
+/// back. A later change of the system clock does not move `when`, only its
+/// conversion.
+fn to_system_time(when: Instant, now: Instant) -> SystemTime {
+    let system_now = SystemTime::now();
+
+    if when >= now {
+        system_now + (when - now)
+    } else {
+        system_now - (now - when)
+    }
+}
+
 async fn clean_expired_tasks(shared: Arc<Shared>) {
     while !shared.is_shutdown() {
+        if shared.active_expire() == ActiveExpire::Sampling {
+            shared.sample_expired_cycle().await;
+
+            tokio::select! {
+                _ = tokio::time::sleep(ACTIVE_EXPIRE_INTERVAL) => {},
+                _ = shared.background_task.notified() => {},
+            }
+            continue;
+        }
+
         let next_expiration = shared.clean_expired_tasks();
 
         match next_expiration {
@@ -192,3 +1149,620 @@ async fn clean_expired_tasks(shared: Arc<Shared>) {
         }
     }
 }
+
+/// Returns the encoding Redis would use for a string holding `data`.
+fn string_encoding(data: &Bytes) -> &'static str {
+    // Integers in canonical form are stored as numbers.
+    let is_integer = !data.is_empty()
+        && data.len() <= 20
+        && std::str::from_utf8(data)
+            .is_ok_and(|s| s.parse::<i64>().is_ok_and(|n| n.to_string() == s));
+    if is_integer {
+        "int"
+    } else if data.len() <= 44 {
+        // Short enough to be embedded in the object header.
+        "embstr"
+    } else {
+        "raw"
+    }
+}
+
+/// Generate a random node ID: 40 hexadecimal characters, as in Redis.
+fn generate_node_id() -> String {
+    use std::hash::{BuildHasher, RandomState};
+
+    // Each `RandomState` is seeded differently, which is random enough to
+    // tell nodes apart.
+    let mut id = String::with_capacity(48);
+    for _ in 0..3 {
+        id.push_str(&format!("{:016x}", RandomState::new().hash_one(&id)));
+    }
+    id.truncate(40);
+    id
+}
+
+/// The error replied when a value is not an integer or an operation on it
+/// overflows.
+fn not_an_integer() -> crate::Error {
+    CommandError::Err("value is not an integer or out of range".to_string()).into()
+}
+
+/// Returns the shared copy of `value` if it is the decimal representation of
+/// a small integer, so that keys holding the same small integer share one
+/// allocation instead of each keeping its own.
+fn shared_integer(value: &Bytes) -> Option<Bytes> {
+    static INTEGERS: OnceLock<Vec<Bytes>> = OnceLock::new();
+
+    // Only the canonical form is shared: no sign, no leading zeros.
+    let canonical = !value.is_empty()
+        && value.len() < 6
+        && value.iter().all(u8::is_ascii_digit)
+        && (value[0] != b'0' || value.len() == 1);
+    if !canonical {
+        return None;
+    }
+
+    let n: usize = std::str::from_utf8(value).ok()?.parse().ok()?;
+    if n >= SHARED_INTEGERS {
+        return None;
+    }
+
+    let integers = INTEGERS.get_or_init(|| {
+        (0..SHARED_INTEGERS)
+            .map(|n| Bytes::from(n.to_string()))
+            .collect()
+    });
+    Some(integers[n].clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn sampling_expiration_removes_due_keys() {
+        let db = Db::new();
+        db.update_settings(|s| {
+            s.active_expire = ActiveExpire::Sampling;
+            Ok(())
+        })
+        .unwrap();
+
+        for i in 0..100 {
+            let key = format!("key:{}", i);
+            db.set(key, Bytes::from("value"), Some(Duration::from_millis(10)))
+                .unwrap();
+        }
+        db.set("kept".to_string(), Bytes::from("value"), None)
+            .unwrap();
+        db.set(
+            "later".to_string(),
+            Bytes::from("value"),
+            Some(Duration::from_secs(3600)),
+        )
+        .unwrap();
+
+        // Keys are picked at random, so the last ones may take a few cycles.
+        tokio::time::sleep(ACTIVE_EXPIRE_INTERVAL * 10).await;
+
+        let state = db.shared.state.lock().unwrap();
+        assert_eq!(1, state.expirations.len());
+        assert_eq!(1, state.volatile.len());
+        assert_eq!(2, state.entries.len());
+        assert!(state.entries.contains_key("kept"));
+        assert!(state.entries.contains_key("later"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wake_background_recomputes_next_expiration() {
+        let db = Db::new();
+
+        db.set(
+            "early".to_string(),
+            Bytes::from("1"),
+            Some(Duration::from_millis(10)),
+        )
+        .unwrap();
+        db.set(
+            "late".to_string(),
+            Bytes::from("2"),
+            Some(Duration::from_millis(100)),
+        )
+        .unwrap();
+        // Let the background task go to sleep until the earliest expiration.
+        tokio::task::yield_now().await;
+
+        {
+            // Swap the earliest expiration for an even earlier one, behind the
+            // back of the background task.
+            let mut state = db.shared.state.lock().unwrap();
+            let (when, key) = state.expirations.first().cloned().unwrap();
+            assert_eq!("early", key);
+            state.remove_expiration(when, &key);
+            state.entries.get_mut("early").unwrap().expires_at = None;
+
+            let when = when - Duration::from_millis(5);
+            state.entries.insert(
+                "earlier".to_string(),
+                Entry {
+                    data: Value::String(Bytes::from("3")),
+                    expires_at: Some(when),
+                    hits: 0,
+                },
+            );
+            state.insert_expiration(when, "earlier".to_string());
+        }
+        db.wake_background();
+
+        tokio::time::sleep(Duration::from_millis(7)).await;
+        {
+            let state = db.shared.state.lock().unwrap();
+            assert!(!state.entries.contains_key("earlier"));
+            assert!(state.entries.contains_key("early"));
+            assert!(state.entries.contains_key("late"));
+        }
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let state = db.shared.state.lock().unwrap();
+        assert!(state.entries.contains_key("early"));
+        assert!(!state.entries.contains_key("late"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn snapshot_excludes_expired_keys() {
+        let db = Db::new();
+
+        db.set("foo".to_string(), Bytes::from("1"), None).unwrap();
+        db.set(
+            "bar".to_string(),
+            Bytes::from("2"),
+            Some(Duration::from_secs(10)),
+        )
+        .unwrap();
+        // Expired, but the background task has not had a chance to run yet.
+        db.set("baz".to_string(), Bytes::from("3"), Some(Duration::ZERO))
+            .unwrap();
+
+        let mut snapshot = db.snapshot();
+        snapshot.sort();
+
+        assert_eq!(
+            vec![
+                (
+                    "bar".to_string(),
+                    Bytes::from("2"),
+                    Some(Duration::from_secs(10))
+                ),
+                ("foo".to_string(), Bytes::from("1"), None),
+            ],
+            snapshot
+        );
+    }
+
+    #[tokio::test]
+    async fn publish_without_subscribers_removes_channel() {
+        let db = Db::new();
+
+        let rx = db.subscribe("hello").unwrap();
+        assert_eq!(1, db.publish("hello".to_string(), Bytes::from("one")));
+
+        // The only subscriber disconnects.
+        drop(rx);
+        assert_eq!(0, db.publish("hello".to_string(), Bytes::from("two")));
+
+        let state = db.shared.state.lock().unwrap();
+        assert!(!state.pub_sub.contains_key("hello"));
+    }
+
+    #[tokio::test]
+    async fn subscriber_receives_every_message_published_after_subscribe() {
+        let db = Db::new();
+
+        let mut rx = db.subscribe("hello").unwrap();
+
+        let publisher = db.clone();
+        tokio::spawn(async move {
+            for i in 0..100 {
+                publisher.publish("hello".to_string(), Bytes::from(i.to_string()));
+            }
+        });
+
+        for i in 0..100 {
+            assert_eq!(Bytes::from(i.to_string()), rx.recv().await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn unsubscribe_releases_channels() {
+        let db = Db::new();
+
+        for i in 0..10_000 {
+            let channel = format!("channel:{}", i);
+            let rx = db.subscribe(&channel).unwrap();
+            drop(rx);
+            db.unsubscribe(&channel);
+        }
+
+        // A channel that still has a subscriber is kept.
+        let _rx = db.subscribe("kept").unwrap();
+        db.unsubscribe("kept");
+
+        let state = db.shared.state.lock().unwrap();
+        assert_eq!(1, state.pub_sub.len());
+        assert!(state.pub_sub.contains_key("kept"));
+    }
+
+    #[tokio::test]
+    async fn subscribe_past_channel_limit_fails() {
+        let db = Db::new();
+        db.update_settings(|s| {
+            s.max_pubsub_channels = 1;
+            Ok(())
+        })
+        .unwrap();
+
+        let _rx = db.subscribe("one").unwrap();
+        assert!(db.subscribe("two").is_err());
+
+        // Existing channels can still be subscribed to.
+        let _rx = db.subscribe("one").unwrap();
+    }
+
+    #[tokio::test]
+    async fn clear_channel_closes_subscribers() {
+        let db = Db::new();
+
+        let mut rx1 = db.subscribe("hello").unwrap();
+        let mut rx2 = db.subscribe("hello").unwrap();
+        assert_eq!(2, db.publish("hello".to_string(), Bytes::from("one")));
+
+        db.clear_channel("hello");
+
+        // Messages published before the channel was cleared are delivered.
+        assert_eq!(Bytes::from("one"), rx1.recv().await.unwrap());
+        assert!(matches!(
+            rx1.recv().await,
+            Err(broadcast::error::RecvError::Closed)
+        ));
+        assert_eq!(Bytes::from("one"), rx2.recv().await.unwrap());
+        assert!(matches!(
+            rx2.recv().await,
+            Err(broadcast::error::RecvError::Closed)
+        ));
+
+        assert_eq!(0, db.publish("hello".to_string(), Bytes::from("two")));
+    }
+
+    #[tokio::test]
+    async fn release_stream_notify_keeps_waited_streams() {
+        let db = Db::new();
+
+        let notify = db.stream_notify("waited");
+        drop(db.stream_notify("released"));
+        db.release_stream_notify("waited");
+        db.release_stream_notify("released");
+
+        {
+            let state = db.shared.state.lock().unwrap();
+            assert_eq!(1, state.stream_waiters.len());
+            assert!(state.stream_waiters.contains_key("waited"));
+        }
+
+        let notified = notify.notified();
+        db.xadd("waited".to_string(), NewId::Auto, vec![]).unwrap();
+        notified.await;
+    }
+
+    #[tokio::test]
+    async fn small_integers_share_allocation() {
+        let db = Db::new();
+
+        db.set("a".to_string(), Bytes::copy_from_slice(b"42"), None)
+            .unwrap();
+        db.set("b".to_string(), Bytes::copy_from_slice(b"42"), None)
+            .unwrap();
+        let a = db.get("a").unwrap().unwrap();
+        let b = db.get("b").unwrap().unwrap();
+        assert_eq!(Bytes::from("42"), a);
+        assert_eq!(a.as_ptr(), b.as_ptr());
+
+        // Only canonical integers below the bound are shared.
+        for value in ["042", "-1", "10000", "4x"] {
+            db.set(
+                "a".to_string(),
+                Bytes::copy_from_slice(value.as_bytes()),
+                None,
+            )
+            .unwrap();
+            db.set(
+                "b".to_string(),
+                Bytes::copy_from_slice(value.as_bytes()),
+                None,
+            )
+            .unwrap();
+            let a = db.get("a").unwrap().unwrap();
+            let b = db.get("b").unwrap().unwrap();
+            assert_eq!(Bytes::from(value), a);
+            assert_ne!(a.as_ptr(), b.as_ptr(), "{}", value);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn describe_reports_type_length_and_ttl() {
+        let db = Db::new();
+
+        db.set(
+            "int".to_string(),
+            Bytes::from("-12"),
+            Some(Duration::from_secs(10)),
+        )
+        .unwrap();
+        db.set("short".to_string(), Bytes::from("hello"), None)
+            .unwrap();
+        db.set("long".to_string(), Bytes::from(vec![b'x'; 45]), None)
+            .unwrap();
+        db.xadd("stream".to_string(), NewId::Auto, vec![]).unwrap();
+        db.xadd("stream".to_string(), NewId::Auto, vec![]).unwrap();
+
+        tokio::time::advance(Duration::from_secs(4)).await;
+
+        let info = |kind, encoding, len, ttl| {
+            Some(KeyInfo {
+                kind,
+                encoding,
+                len,
+                ttl,
+            })
+        };
+        assert_eq!(
+            info("string", "int", 3, Some(Duration::from_secs(6))),
+            db.describe("int")
+        );
+        assert_eq!(info("string", "embstr", 5, None), db.describe("short"));
+        assert_eq!(info("string", "raw", 45, None), db.describe("long"));
+        assert_eq!(info("stream", "stream", 2, None), db.describe("stream"));
+        assert_eq!(None, db.describe("missing"));
+    }
+
+    #[tokio::test]
+    async fn iter_walks_a_snapshot() {
+        let db = Db::new();
+
+        db.set("foo".to_string(), Bytes::from("1"), None).unwrap();
+        db.set("bar".to_string(), Bytes::from("2"), None).unwrap();
+        db.set("gone".to_string(), Bytes::from("3"), Some(Duration::ZERO))
+            .unwrap();
+
+        let iter = db.iter();
+
+        // Changes after the iterator is created are not seen.
+        db.set("foo".to_string(), Bytes::from("changed"), None)
+            .unwrap();
+        db.set("baz".to_string(), Bytes::from("4"), None).unwrap();
+
+        let mut keys: Vec<_> = iter.collect();
+        keys.sort();
+        assert_eq!(
+            vec![
+                ("bar".to_string(), Bytes::from("2")),
+                ("foo".to_string(), Bytes::from("1")),
+            ],
+            keys
+        );
+    }
+
+    /// A clock that only moves when told to.
+    struct FakeClock {
+        now: Mutex<Instant>,
+    }
+
+    impl FakeClock {
+        fn advance(&self, duration: Duration) {
+            *self.now.lock().unwrap() += duration;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            *self.now.lock().unwrap()
+        }
+    }
+
+    #[tokio::test]
+    async fn key_expires_at_fake_clock_deadline() {
+        let clock = Arc::new(FakeClock {
+            now: Mutex::new(Instant::now()),
+        });
+        let db = Db::with_clock(clock.clone());
+
+        db.set(
+            "foo".to_string(),
+            Bytes::from("bar"),
+            Some(Duration::from_secs(10)),
+        )
+        .unwrap();
+
+        clock.advance(Duration::from_millis(9_999));
+        db.shared.clean_expired_tasks();
+        assert_eq!(Some(Bytes::from("bar")), db.get("foo").unwrap());
+        assert_eq!(
+            vec![("foo".to_string(), Duration::from_millis(1))],
+            db.expirations(10)
+        );
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(None, db.shared.clean_expired_tasks());
+        assert_eq!(None, db.get("foo").unwrap());
+    }
+
+    #[tokio::test]
+    async fn get_or_insert_with_computes_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Barrier;
+
+        let db = Db::new();
+        let computed = AtomicUsize::new(0);
+        let barrier = Barrier::new(8);
+
+        let values: Vec<Bytes> = std::thread::scope(|scope| {
+            let callers: Vec<_> = (0..8)
+                .map(|i| {
+                    let (db, computed, barrier) = (&db, &computed, &barrier);
+                    scope.spawn(move || {
+                        barrier.wait();
+                        db.get_or_insert_with("foo".to_string(), None, || {
+                            computed.fetch_add(1, Ordering::SeqCst);
+                            std::thread::sleep(Duration::from_millis(10));
+                            Bytes::from(format!("computed by {}", i))
+                        })
+                        .unwrap()
+                    })
+                })
+                .collect();
+
+            callers.into_iter().map(|c| c.join().unwrap()).collect()
+        });
+
+        assert_eq!(1, computed.load(Ordering::SeqCst));
+        assert!(values.iter().all(|value| *value == values[0]));
+        assert_eq!(Some(values[0].clone()), db.get("foo").unwrap());
+
+        db.xadd("events".to_string(), NewId::Auto, vec![]).unwrap();
+        assert!(db
+            .get_or_insert_with("events".to_string(), None, || Bytes::from("x"))
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn get_removes_expired_key() {
+        let clock = Arc::new(FakeClock {
+            now: Mutex::new(Instant::now()),
+        });
+        let db = Db::with_clock(clock.clone());
+
+        db.set(
+            "foo".to_string(),
+            Bytes::from("bar"),
+            Some(Duration::from_millis(10)),
+        )
+        .unwrap();
+
+        // The background task sleeps on the Tokio clock, so it does not run.
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(None, db.get("foo").unwrap());
+
+        let state = db.shared.state.lock().unwrap();
+        assert!(state.entries.is_empty());
+        assert!(state.expirations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn overflowing_expiration_is_rejected() {
+        let db = Db::new();
+        let far = Some(Duration::from_secs(i64::MAX as u64));
+
+        assert!(db.set("foo".to_string(), Bytes::from("bar"), far).is_err());
+        assert!(db
+            .set_if(
+                "foo".to_string(),
+                Bytes::from("bar"),
+                far,
+                SetIf::Always,
+                false
+            )
+            .is_err());
+        assert!(db
+            .get_or_insert_with("foo".to_string(), far, || Bytes::from("bar"))
+            .is_err());
+
+        // The lock is not poisoned.
+        assert_eq!(None, db.get("foo").unwrap());
+        assert!(db.shared.state.lock().unwrap().expirations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_stream_is_removed_on_access() {
+        let clock = Arc::new(FakeClock {
+            now: Mutex::new(Instant::now()),
+        });
+        let db = Db::with_clock(clock.clone());
+        let id = |ms| NewId::Explicit(StreamId { ms, seq: 0 });
+        let field = || vec![(Bytes::from("a"), Bytes::from("1"))];
+
+        db.xadd("s".to_string(), id(5), field()).unwrap();
+        assert!(db.expire("s", Duration::from_millis(10)));
+        clock.advance(Duration::from_millis(20));
+
+        assert_eq!(0, db.xlen("s").unwrap());
+        assert!(db
+            .xrange("s", StreamId::MIN, StreamId::MAX, None)
+            .unwrap()
+            .is_empty());
+        assert!(db.shared.state.lock().unwrap().entries.is_empty());
+
+        // XADD starts a new stream, without the old deadline.
+        db.xadd("t".to_string(), id(5), field()).unwrap();
+        assert!(db.expire("t", Duration::from_millis(10)));
+        clock.advance(Duration::from_millis(20));
+
+        assert_eq!(
+            StreamId { ms: 1, seq: 0 },
+            db.xadd("t".to_string(), id(1), field()).unwrap()
+        );
+        assert_eq!(1, db.xlen("t").unwrap());
+        assert_eq!(Some(None), db.expire_time("t"));
+        assert!(db.shared.state.lock().unwrap().expirations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn expire_replaces_expiration() {
+        let clock = Arc::new(FakeClock {
+            now: Mutex::new(Instant::now()),
+        });
+        let db = Db::with_clock(clock.clone());
+
+        assert!(!db.expire("foo", Duration::from_secs(10)));
+
+        db.set("foo".to_string(), Bytes::from("bar"), None).unwrap();
+        assert!(db.expire("foo", Duration::from_secs(10)));
+        assert_eq!(
+            vec![("foo".to_string(), Duration::from_secs(10))],
+            db.expirations(10)
+        );
+
+        assert!(db.expire("foo", Duration::from_secs(5)));
+        assert_eq!(
+            vec![("foo".to_string(), Duration::from_secs(5))],
+            db.expirations(10)
+        );
+        assert_eq!(Some(Bytes::from("bar")), db.get("foo").unwrap());
+
+        clock.advance(Duration::from_secs(5));
+        assert!(!db.expire("foo", Duration::from_secs(10)));
+        assert_eq!(None, db.get("foo").unwrap());
+    }
+
+    #[tokio::test]
+    async fn expired_key_does_not_exist_before_removal() {
+        let clock = Arc::new(FakeClock {
+            now: Mutex::new(Instant::now()),
+        });
+        let db = Db::with_clock(clock.clone());
+
+        db.set(
+            "foo".to_string(),
+            Bytes::from("bar"),
+            Some(Duration::from_secs(10)),
+        )
+        .unwrap();
+        db.set("bar".to_string(), Bytes::from("baz"), None).unwrap();
+        assert!(db.exists("foo"));
+
+        clock.advance(Duration::from_secs(10));
+        assert!(!db.exists("foo"));
+        assert!(db.shared.state.lock().unwrap().entries.contains_key("foo"));
+
+        assert!(db.exists("bar"));
+        assert!(!db.exists("baz"));
+    }
+}
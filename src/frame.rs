@@ -8,15 +8,90 @@ pub enum Frame {
     Error(String),
     Integer(i64),
     Bulk(Bytes),
+    /// The null bulk string, `$-1`.
     Null,
+    /// The null array, `*-1`, replied where an array is expected, e.g. by
+    /// `XREAD` when there are no entries.
+    NullArray,
     Array(Vec<Frame>),
+    /// A RESP3 double, `,<value>`, including `inf`, `-inf` and `nan`.
+    Double(f64),
+    /// A RESP3 boolean, `#t` or `#f`.
+    Boolean(bool),
+    /// A RESP3 map, `%<pairs>` followed by each key and value.
+    Map(Vec<(Frame, Frame)>),
+    /// A RESP3 integer of any size, `(<digits>`.
+    BigNumber(String),
+    /// A RESP3 push, `><len>` followed by each element: data sent by the
+    /// server out of band, such as pub/sub messages.
+    Push(Vec<Frame>),
 }
 
+/// Default length above which bulk strings are rejected, as in Redis.
+pub const DEFAULT_MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+
+/// Number of elements above which arrays are rejected, as in Redis.
+const MAX_ARRAY_LEN: i64 = i32::MAX as i64;
+
 impl Frame {
-    /// Parse a frame from the given buffer.
+    /// Parse a frame from the given buffer, as sent on a RESP2 connection.
     /// Return how many bytes should be consumed and the frame if succeed.
-    pub fn parse(mut buf: &[u8]) -> Result<(usize, Frame), Error> {
+    pub fn parse(buf: &[u8]) -> Result<(usize, Frame), Error> {
+        Frame::parse_with_limit(buf, DEFAULT_MAX_BULK_LEN, 2)
+    }
+
+    /// Parse a frame like `parse`, rejecting any bulk string declared longer
+    /// than `max_bulk_len` before waiting for its body. The RESP3 types are
+    /// only accepted when `protocol` is `3`, as negotiated with `HELLO`.
+    ///
+    /// A command may also be sent inline, as typed in a terminal, and is then
+    /// parsed into an array of bulk strings. See `parse_inline`.
+    pub fn parse_with_limit(
+        buf: &[u8],
+        max_bulk_len: usize,
+        protocol: u8,
+    ) -> Result<(usize, Frame), Error> {
+        // Blank lines, as sent by pressing enter in a terminal, are skipped.
+        let blank = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+        if blank > MAX_INLINE_LEN {
+            return Err(Error::Other(TOO_BIG_INLINE.into()));
+        }
+
+        let buf = &buf[blank..];
+        let (advance, frame) = match buf.first() {
+            // Inline commands start with the command name, where RESP frames
+            // start with a type byte that is never a letter.
+            Some(byte) if byte.is_ascii_alphabetic() => parse_inline(buf)?,
+            _ => Frame::parse_value(buf, max_bulk_len, protocol)?,
+        };
+
+        Ok((blank + advance, frame))
+    }
+
+    /// Parse a RESP frame, including the elements of arrays and maps.
+    fn parse_value(
+        mut buf: &[u8],
+        max_bulk_len: usize,
+        protocol: u8,
+    ) -> Result<(usize, Frame), Error> {
+        if !buf.has_remaining() {
+            return Err(Error::Incomplete);
+        }
+
         match buf.get_u8() {
+            // Types only defined by RESP3, which must be negotiated first.
+            byte @ (b'_' | b',' | b'#' | b'!' | b'=' | b'(' | b'%' | b'~' | b'>' | b'|')
+                if protocol < 3 =>
+            {
+                return Err(Error::Other(
+                    format!(
+                        "protocol error; got RESP3 type byte '{}' on a RESP{} connection, \
+                         is the client using the wrong protocol version?",
+                        byte as char, protocol
+                    )
+                    .into(),
+                ));
+            }
             b'+' => {
                 if let Some((advance, line)) = get_line(buf) {
                     let s = String::from_utf8(line.to_vec())?;
@@ -46,6 +121,16 @@ impl Frame {
                         return Err(Error::Other(INVALID_FORMAT.into()));
                     }
 
+                    if len as usize > max_bulk_len {
+                        return Err(Error::Other(
+                            format!(
+                                "protocol error; bulk length {} exceeds proto-max-bulk-len {}",
+                                len, max_bulk_len
+                            )
+                            .into(),
+                        ));
+                    }
+
                     let data_buf = &buf[advance..];
                     let len = len as usize;
                     if data_buf.len() >= len + 2
@@ -57,31 +142,127 @@ impl Frame {
                     }
                 }
             }
-            b'*' => {
+            byte @ (b'*' | b'>') => {
                 let mut total_advance = 1;
                 if let Some((decimal_advance, n_elements)) = get_decimal(buf) {
+                    if n_elements == -1 && byte == b'*' {
+                        return Ok((total_advance + decimal_advance, Frame::NullArray));
+                    }
+
                     if n_elements < 0 {
                         return Err(Error::Other(INVALID_FORMAT.into()));
                     }
 
+                    if n_elements > MAX_ARRAY_LEN {
+                        return Err(Error::Other(
+                            "protocol error; invalid multibulk length".into(),
+                        ));
+                    }
+
                     total_advance += decimal_advance;
                     buf.advance(decimal_advance);
 
-                    let mut array = Vec::with_capacity(n_elements as usize);
+                    // Each element takes at least 3 bytes, so allocate no more
+                    // than the buffered bytes can fill. A huge header alone
+                    // would otherwise allocate a huge array.
+                    let capacity = (n_elements as usize).min(buf.len() / 3);
+                    let mut array = Vec::with_capacity(capacity);
                     for _ in 0..n_elements {
-                        let (advance, frame) = Frame::parse(buf)?;
+                        let (advance, frame) = Frame::parse_value(buf, max_bulk_len, protocol)?;
                         total_advance += advance;
                         buf.advance(advance);
                         array.push(frame);
                     }
 
-                    return Ok((total_advance, Frame::Array(array)));
+                    let frame = match byte {
+                        b'>' => Frame::Push(array),
+                        _ => Frame::Array(array),
+                    };
+                    return Ok((total_advance, frame));
+                }
+            }
+            b',' => {
+                if let Some((advance, line)) = get_line(buf) {
+                    let double = std::str::from_utf8(line)
+                        .ok()
+                        .and_then(|s| s.parse::<f64>().ok());
+                    return match double {
+                        Some(double) => Ok((1 + advance, Frame::Double(double))),
+                        None => Err(Error::Other("protocol error; invalid double".into())),
+                    };
+                }
+            }
+            b'#' => {
+                if let Some((advance, line)) = get_line(buf) {
+                    return match line {
+                        b"t" => Ok((1 + advance, Frame::Boolean(true))),
+                        b"f" => Ok((1 + advance, Frame::Boolean(false))),
+                        _ => Err(Error::Other("protocol error; invalid boolean".into())),
+                    };
+                }
+            }
+            b'(' => {
+                if let Some((advance, line)) = get_line(buf) {
+                    let digits = line.strip_prefix(b"-").unwrap_or(line);
+                    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+                        return Err(Error::Other("protocol error; invalid big number".into()));
+                    }
+                    let s = String::from_utf8(line.to_vec())?;
+                    return Ok((1 + advance, Frame::BigNumber(s)));
+                }
+            }
+            b'%' => {
+                let mut total_advance = 1;
+                if let Some((decimal_advance, n_pairs)) = get_decimal(buf) {
+                    if !(0..=MAX_ARRAY_LEN).contains(&n_pairs) {
+                        return Err(Error::Other(INVALID_FORMAT.into()));
+                    }
+
+                    total_advance += decimal_advance;
+                    buf.advance(decimal_advance);
+
+                    // Each pair takes at least 6 bytes, see arrays.
+                    let capacity = (n_pairs as usize).min(buf.len() / 6);
+                    let mut map = Vec::with_capacity(capacity);
+                    for _ in 0..n_pairs {
+                        let (advance, key) = Frame::parse_value(buf, max_bulk_len, protocol)?;
+                        total_advance += advance;
+                        buf.advance(advance);
+                        let (advance, value) = Frame::parse_value(buf, max_bulk_len, protocol)?;
+                        total_advance += advance;
+                        buf.advance(advance);
+                        map.push((key, value));
+                    }
+
+                    return Ok((total_advance, Frame::Map(map)));
                 }
             }
-            _ => unimplemented!(),
+            b'_' => {
+                if let Some((advance, line)) = get_line(buf) {
+                    if !line.is_empty() {
+                        return Err(Error::Other(INVALID_FORMAT.into()));
+                    }
+                    return Ok((1 + advance, Frame::Null));
+                }
+            }
+            // RESP3 types that are not supported.
+            byte @ (b'!' | b'=' | b'~' | b'|') => {
+                return Err(Error::Other(
+                    format!(
+                        "protocol error; RESP3 type byte '{}' is not supported on a RESP{} connection",
+                        byte as char, protocol
+                    )
+                    .into(),
+                ));
+            }
+            byte => {
+                return Err(Error::Other(
+                    format!("protocol error; invalid frame type byte {:?}", byte as char).into(),
+                ));
+            }
         }
 
-        Err(Error::Incomplete.into())
+        Err(Error::Incomplete)
     }
 
     pub fn parse_simple(buf: &[u8]) -> crate::Result<Frame> {
@@ -96,7 +277,7 @@ impl Frame {
 
     pub fn push_bulk(&mut self, bytes: Bytes) {
         match self {
-            Frame::Array(frames) => {
+            Frame::Array(frames) | Frame::Push(frames) => {
                 frames.push(Frame::Bulk(bytes));
             }
             _ => panic!("not an array frame"),
@@ -105,7 +286,7 @@ impl Frame {
 
     pub fn push_int(&mut self, value: i64) {
         match self {
-            Frame::Array(frames) => {
+            Frame::Array(frames) | Frame::Push(frames) => {
                 frames.push(Frame::Integer(value));
             }
             _ => panic!("not an array frame"),
@@ -116,11 +297,89 @@ impl Frame {
         Frame::Array(vec![])
     }
 
+    /// Start building the frame of command `name`.
+    pub fn cmd(name: &'static str) -> FrameBuilder {
+        FrameBuilder {
+            frames: vec![Frame::Bulk(Bytes::from_static(name.as_bytes()))],
+        }
+    }
+
+    /// Returns the number of bytes `Connection::write_frame` writes for this
+    /// frame on a connection speaking `protocol`, without encoding it.
+    pub fn encoded_len(&self, protocol: u8) -> usize {
+        let len = |frame: &Frame| frame.encoded_len(protocol);
+
+        // Every frame is a type byte followed by a line ending with CRLF.
+        // Under RESP2, RESP3 types are written as their RESP2 counterparts,
+        // see `Connection::write_value`.
+        match self {
+            Frame::Simple(s) | Frame::Error(s) => 1 + s.len() + 2,
+            Frame::Integer(n) => 1 + decimal_len(*n) + 2,
+            Frame::Bulk(data) => 1 + decimal_len(data.len() as i64) + 2 + data.len() + 2,
+            Frame::Null | Frame::NullArray if protocol >= 3 => 3,
+            Frame::Null | Frame::NullArray => 5,
+            Frame::Array(frames) | Frame::Push(frames) => {
+                let header = 1 + decimal_len(frames.len() as i64) + 2;
+                header + frames.iter().map(len).sum::<usize>()
+            }
+            Frame::Double(double) if protocol >= 3 => 1 + format_double(*double).len() + 2,
+            Frame::Double(double) => len(&Frame::Bulk(format_double(*double).into())),
+            Frame::Boolean(_) => 4,
+            Frame::Map(pairs) => {
+                let count = if protocol >= 3 {
+                    pairs.len()
+                } else {
+                    2 * pairs.len()
+                };
+                let header = 1 + decimal_len(count as i64) + 2;
+                header
+                    + pairs
+                        .iter()
+                        .map(|(key, value)| len(key) + len(value))
+                        .sum::<usize>()
+            }
+            Frame::BigNumber(digits) if protocol >= 3 => 1 + digits.len() + 2,
+            Frame::BigNumber(digits) => len(&Frame::Bulk(digits.clone().into())),
+        }
+    }
+
     pub(crate) fn to_error(&self) -> crate::Error {
         format!("unexpected frame: {}", self).into()
     }
 }
 
+/// Builds the array frame of a command, one argument at a time:
+/// `Frame::cmd("set").arg(key).arg(value).arg_int(ms).build()`.
+#[derive(Debug)]
+pub struct FrameBuilder {
+    frames: Vec<Frame>,
+}
+
+impl FrameBuilder {
+    /// Append a bulk string argument.
+    pub fn arg(mut self, arg: impl Into<Bytes>) -> FrameBuilder {
+        self.frames.push(Frame::Bulk(arg.into()));
+        self
+    }
+
+    /// Append every item of `args` as a bulk string argument.
+    pub fn args<T: Into<Bytes>>(mut self, args: impl IntoIterator<Item = T>) -> FrameBuilder {
+        self.frames
+            .extend(args.into_iter().map(|arg| Frame::Bulk(arg.into())));
+        self
+    }
+
+    /// Append an integer argument.
+    pub fn arg_int(mut self, value: i64) -> FrameBuilder {
+        self.frames.push(Frame::Integer(value));
+        self
+    }
+
+    pub fn build(self) -> Frame {
+        Frame::Array(self.frames)
+    }
+}
+
 impl PartialEq<&str> for Frame {
     fn eq(&self, other: &&str) -> bool {
         match self {
@@ -153,8 +412,8 @@ impl fmt::Display for Frame {
                 Ok(string) => string.fmt(fmt),
                 Err(_) => write!(fmt, "{:?}", msg),
             },
-            Frame::Null => "(nil)".fmt(fmt),
-            Frame::Array(parts) => {
+            Frame::Null | Frame::NullArray => "(nil)".fmt(fmt),
+            Frame::Array(parts) | Frame::Push(parts) => {
                 for (i, part) in parts.iter().enumerate() {
                     if i > 0 {
                         // use space as the array element display separator
@@ -166,12 +425,114 @@ impl fmt::Display for Frame {
 
                 Ok(())
             }
+            Frame::Double(double) => format_double(*double).fmt(fmt),
+            Frame::Boolean(value) => value.fmt(fmt),
+            Frame::Map(pairs) => {
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, " ")?;
+                    }
+
+                    write!(fmt, "{} {}", key, value)?;
+                }
+
+                Ok(())
+            }
+            Frame::BigNumber(digits) => digits.fmt(fmt),
         }
     }
 }
 
+/// Number of characters in the decimal representation of `n`.
+fn decimal_len(n: i64) -> usize {
+    let sign = (n < 0) as usize;
+    let mut n = n.unsigned_abs();
+    let mut len = 1;
+    while n >= 10 {
+        n /= 10;
+        len += 1;
+    }
+    sign + len
+}
+
 static INVALID_FORMAT: &str = "protocol error; invalid frame format";
 
+/// Length above which an inline command is rejected, as in Redis.
+const MAX_INLINE_LEN: usize = 64 * 1024;
+
+static TOO_BIG_INLINE: &str = "protocol error; too big inline request";
+
+/// Parse a command sent inline: arguments separated by whitespace on a line
+/// ending with `\n` or `\r\n`, e.g. `SET foo "hello world"`.
+fn parse_inline(buf: &[u8]) -> Result<(usize, Frame), Error> {
+    let end = match buf.iter().position(|&b| b == b'\n') {
+        Some(end) => end,
+        None if buf.len() > MAX_INLINE_LEN => return Err(Error::Other(TOO_BIG_INLINE.into())),
+        None => return Err(Error::Incomplete),
+    };
+
+    let line = &buf[..end];
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let args = match split_inline(line) {
+        Some(args) => args,
+        None => {
+            return Err(Error::Other(
+                "protocol error; unbalanced quotes in request".into(),
+            ))
+        }
+    };
+
+    let args = args
+        .into_iter()
+        .map(|arg| Frame::Bulk(arg.into()))
+        .collect();
+    Ok((end + 1, Frame::Array(args)))
+}
+
+/// Split an inline command into its arguments. An argument may be wrapped in
+/// double quotes to contain whitespace, with `\` escaping the next character
+/// (`\n`, `\r` and `\t` stand for control characters). Returns `None` if a
+/// quote is left open or not followed by whitespace.
+fn split_inline(line: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut args = Vec::new();
+    let mut bytes = line.iter().copied().peekable();
+
+    loop {
+        while bytes.next_if(u8::is_ascii_whitespace).is_some() {}
+
+        let mut arg = Vec::new();
+        match bytes.peek() {
+            None => return Some(args),
+            Some(b'"') => {
+                bytes.next();
+                loop {
+                    match bytes.next()? {
+                        b'"' => break,
+                        b'\\' => arg.push(match bytes.next()? {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b => b,
+                        }),
+                        b => arg.push(b),
+                    }
+                }
+
+                if bytes.peek().is_some_and(|b| !b.is_ascii_whitespace()) {
+                    return None;
+                }
+            }
+            Some(_) => {
+                while let Some(b) = bytes.next_if(|b| !b.is_ascii_whitespace()) {
+                    arg.push(b);
+                }
+            }
+        }
+
+        args.push(arg);
+    }
+}
+
 /// Find a new-line terminated decimal.
 /// Return how many bytes should be consumed and the line itself.
 pub fn get_decimal(buf: &[u8]) -> Option<(usize, i64)> {
@@ -185,7 +546,7 @@ pub fn get_decimal(buf: &[u8]) -> Option<(usize, i64)> {
 /// Return how many bytes should be consumed and the line itself.
 // todo: change return type.
 pub fn get_line(buf: &[u8]) -> Option<(usize, &[u8])> {
-    for i in 0..buf.len() - 1 {
+    for i in 0..buf.len().saturating_sub(1) {
         if buf[i] == b'\r' && buf[i + 1] == b'\n' {
             return Some((i + 2, &buf[..i]));
         }
@@ -193,6 +554,18 @@ pub fn get_line(buf: &[u8]) -> Option<(usize, &[u8])> {
     None
 }
 
+/// Format a double as RESP3 writes it, with `inf`, `-inf` and `nan` for the
+/// values that have no decimal representation.
+pub(crate) fn format_double(double: f64) -> String {
+    if double.is_nan() {
+        "nan".to_string()
+    } else if double.is_infinite() {
+        if double > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        double.to_string()
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     Incomplete,
@@ -220,6 +593,11 @@ impl From<FromUtf8Error> for Error {
 mod tests {
     use super::*;
 
+    /// Parse a frame as sent on a connection that negotiated RESP3.
+    fn parse3(buf: &[u8]) -> Result<(usize, Frame), Error> {
+        Frame::parse_with_limit(buf, DEFAULT_MAX_BULK_LEN, 3)
+    }
+
     #[test]
     fn get_signed_decimal() {
         let buf = b"+123\r\n";
@@ -273,6 +651,126 @@ mod tests {
         assert_eq!(frame, Frame::Null);
     }
 
+    #[test]
+    fn parse_null_array() {
+        let buf = b"*-1\r\n";
+        let (advance, frame) = Frame::parse(buf).unwrap();
+        assert_eq!(advance, 5);
+        assert_eq!(frame, Frame::NullArray);
+
+        assert!(Frame::parse(b"*-2\r\n").is_err());
+    }
+
+    #[test]
+    fn parse_double() {
+        let (advance, frame) = parse3(b",2.25\r\n").unwrap();
+        assert_eq!(advance, 7);
+        assert_eq!(frame, Frame::Double(2.25));
+
+        assert_eq!(Frame::Double(-2.0), parse3(b",-2\r\n").unwrap().1);
+        assert_eq!(Frame::Double(1e10), parse3(b",1e10\r\n").unwrap().1);
+        assert_eq!(Frame::Double(f64::INFINITY), parse3(b",inf\r\n").unwrap().1);
+        assert_eq!(
+            Frame::Double(f64::NEG_INFINITY),
+            parse3(b",-inf\r\n").unwrap().1
+        );
+        assert!(matches!(
+            parse3(b",nan\r\n").unwrap().1,
+            Frame::Double(double) if double.is_nan()
+        ));
+
+        assert!(matches!(parse3(b",abc\r\n"), Err(Error::Other(_))));
+        assert!(matches!(parse3(b",3.1"), Err(Error::Incomplete)));
+    }
+
+    #[test]
+    fn parse_resp3_types_need_resp3() {
+        for buf in [
+            &b"_\r\n"[..],
+            b",2.5\r\n",
+            b"#t\r\n",
+            b"(12\r\n",
+            b"%0\r\n",
+            b">0\r\n",
+        ] {
+            assert!(parse3(buf).is_ok(), "{:?}", buf);
+            assert!(
+                matches!(Frame::parse(buf), Err(Error::Other(_))),
+                "{:?}",
+                buf
+            );
+        }
+    }
+
+    #[test]
+    fn parse_resp3_null() {
+        assert_eq!((3, Frame::Null), parse3(b"_\r\n").unwrap());
+        assert!(matches!(parse3(b"_x\r\n"), Err(Error::Other(_))));
+    }
+
+    #[test]
+    fn parse_push() {
+        let (advance, frame) = parse3(b">2\r\n+message\r\n:1\r\n").unwrap();
+        assert_eq!(18, advance);
+        assert_eq!(
+            Frame::Push(vec![
+                Frame::Simple("message".to_string()),
+                Frame::Integer(1)
+            ]),
+            frame
+        );
+        assert!(matches!(parse3(b">-1\r\n"), Err(Error::Other(_))));
+    }
+
+    #[test]
+    fn parse_boolean() {
+        assert_eq!((4, Frame::Boolean(true)), parse3(b"#t\r\n").unwrap());
+        assert_eq!((4, Frame::Boolean(false)), parse3(b"#f\r\n").unwrap());
+        assert!(matches!(parse3(b"#x\r\n"), Err(Error::Other(_))));
+    }
+
+    #[test]
+    fn parse_big_number() {
+        let buf = b"(3492890328409238509324850943850943825024385\r\n";
+        let (advance, frame) = parse3(buf).unwrap();
+        assert_eq!(advance, buf.len());
+        assert_eq!(
+            frame,
+            Frame::BigNumber("3492890328409238509324850943850943825024385".to_string())
+        );
+
+        assert_eq!(
+            Frame::BigNumber("-12".to_string()),
+            parse3(b"(-12\r\n").unwrap().1
+        );
+        assert!(matches!(parse3(b"(12a\r\n"), Err(Error::Other(_))));
+        assert!(matches!(parse3(b"(-\r\n"), Err(Error::Other(_))));
+    }
+
+    #[test]
+    fn parse_map() {
+        let buf = b"%2\r\n+first\r\n:1\r\n$6\r\nsecond\r\n#t\r\n";
+        let (advance, frame) = parse3(buf).unwrap();
+        assert_eq!(advance, buf.len());
+        assert_eq!(
+            frame,
+            Frame::Map(vec![
+                (Frame::Simple("first".to_string()), Frame::Integer(1)),
+                (
+                    Frame::Bulk(Bytes::from_static(b"second")),
+                    Frame::Boolean(true)
+                ),
+            ])
+        );
+
+        // A key without its value.
+        assert!(matches!(
+            parse3(b"%1\r\n+first\r\n"),
+            Err(Error::Incomplete)
+        ));
+        assert_eq!(Frame::Map(vec![]), parse3(b"%0\r\n").unwrap().1);
+    }
+
     #[test]
     fn parse_bulk_string() {
         let buf = b"$6\r\nfoobar\r\n";
@@ -281,6 +779,124 @@ mod tests {
         assert_eq!(frame, Frame::Bulk(Bytes::from_static(b"foobar")));
     }
 
+    #[test]
+    fn parse_bulk_string_containing_crlf() {
+        let buf = b"$8\r\nfoo\r\nbar\r\n";
+        let (advance, frame) = Frame::parse(buf).unwrap();
+        assert_eq!(advance, 14);
+        assert_eq!(frame, Frame::Bulk(Bytes::from_static(b"foo\r\nbar")));
+    }
+
+    #[test]
+    fn parse_incomplete_bulk_string_containing_crlf() {
+        // The body holds a CRLF, but is shorter than the declared length.
+        let buf = b"$8\r\nfoo\r\n";
+        assert!(matches!(Frame::parse(buf), Err(Error::Incomplete)));
+    }
+
+    #[test]
+    fn get_line_on_short_buffers() {
+        assert_eq!(None, get_line(b""));
+        assert_eq!(None, get_line(b"\r"));
+        assert_eq!(Some((2, &b""[..])), get_line(b"\r\n"));
+        assert_eq!(Some((4, &b"OK"[..])), get_line(b"OK\r\n+"));
+    }
+
+    #[test]
+    fn parse_truncated_frames() {
+        for buf in [&b"+"[..], b"-", b":", b"$", b"*", b"+OK\r", b"$3\r"] {
+            assert!(
+                matches!(Frame::parse(buf), Err(Error::Incomplete)),
+                "{:?}",
+                buf
+            );
+        }
+    }
+
+    #[test]
+    fn parse_unknown_type_byte_is_an_error() {
+        assert!(matches!(Frame::parse(b"~3\r\n"), Err(Error::Other(_))));
+        assert!(matches!(parse3(b"~3\r\n"), Err(Error::Other(_))));
+        assert!(matches!(Frame::parse(b"\x00garbage"), Err(Error::Other(_))));
+        assert!(matches!(
+            Frame::parse(b"*2\r\n$3\r\nGET\r\n@key\r\n"),
+            Err(Error::Other(_))
+        ));
+    }
+
+    #[test]
+    fn parse_inline_commands() {
+        let (advance, frame) = Frame::parse(b"SET foo bar\r\n").unwrap();
+        assert_eq!(13, advance);
+        assert_eq!(crate::frame!["SET", "foo", "bar"], frame);
+
+        let (advance, frame) = Frame::parse(b"PING\r\n").unwrap();
+        assert_eq!(6, advance);
+        assert_eq!(crate::frame!["PING"], frame);
+
+        // Blank lines are skipped and a bare `\n` ends the line.
+        let (advance, frame) = Frame::parse(b"\r\n  ping\n").unwrap();
+        assert_eq!(9, advance);
+        assert_eq!(crate::frame!["ping"], frame);
+
+        let (_, frame) = Frame::parse(b"SET k \"a b\\\"c\" \"\"\r\n").unwrap();
+        assert_eq!(crate::frame!["SET", "k", "a b\"c", ""], frame);
+
+        assert!(matches!(Frame::parse(b"PING"), Err(Error::Incomplete)));
+        assert!(matches!(
+            Frame::parse(b"SET k \"open\r\n"),
+            Err(Error::Other(_))
+        ));
+        assert!(matches!(
+            Frame::parse(&[b'a'; MAX_INLINE_LEN + 1]),
+            Err(Error::Other(_))
+        ));
+    }
+
+    #[test]
+    fn parse_array_with_crlf_inside_bulk() {
+        let buf = b"*2\r\n$4\r\n\r\n\r\n\r\n:1\r\n";
+        let (advance, frame) = Frame::parse(buf).unwrap();
+        assert_eq!(advance, buf.len());
+        assert_eq!(
+            frame,
+            Frame::Array(vec![
+                Frame::Bulk(Bytes::from_static(b"\r\n\r\n")),
+                Frame::Integer(1),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_huge_array_header() {
+        // Incomplete, without allocating for the declared elements.
+        let buf = b"*2000000000\r\n:1\r\n";
+        assert!(matches!(Frame::parse(buf), Err(Error::Incomplete)));
+
+        let buf = b"*3000000000\r\n";
+        assert!(matches!(Frame::parse(buf), Err(Error::Other(_))));
+    }
+
+    #[test]
+    fn parse_bulk_string_over_limit() {
+        // Rejected from the header alone, without waiting for the body.
+        let buf = b"$11\r\nhello";
+        assert!(matches!(
+            Frame::parse_with_limit(buf, 10, 2),
+            Err(Error::Other(_))
+        ));
+
+        // Also inside arrays.
+        let buf = b"*1\r\n$11\r\nhello world\r\n";
+        assert!(matches!(
+            Frame::parse_with_limit(buf, 10, 2),
+            Err(Error::Other(_))
+        ));
+
+        let buf = b"$10\r\nhelloworld\r\n";
+        assert!(Frame::parse_with_limit(buf, 10, 2).is_ok());
+    }
+
     #[test]
     fn parse_array() {
         let buf = b"*2\r\n$5\r\nhello\r\n$5\r\nworld\r\n";
@@ -295,6 +911,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn builder_matches_hand_built_frames() {
+        use crate::cmd::{Get, Publish, Set};
+        use std::time::Duration;
+
+        let mut set = Frame::array();
+        set.push_bulk(Bytes::from("set".as_bytes()));
+        set.push_bulk(Bytes::from("foo".as_bytes()));
+        set.push_bulk(Bytes::from("bar"));
+        set.push_bulk(Bytes::from("px".as_bytes()));
+        set.push_int(1500);
+        let built = Set::new("foo", Bytes::from("bar"), Some(Duration::from_millis(1500)));
+        assert_eq!(set, built.into_frame());
+
+        let mut get = Frame::array();
+        get.push_bulk(Bytes::from("get".as_bytes()));
+        get.push_bulk(Bytes::from("foo".as_bytes()));
+        assert_eq!(get, Get::new("foo").into_frame());
+
+        let mut publish = Frame::array();
+        publish.push_bulk(Bytes::from("publish".as_bytes()));
+        publish.push_bulk(Bytes::from("news".as_bytes()));
+        publish.push_bulk(Bytes::from("hello"));
+        assert_eq!(
+            publish,
+            Publish::new("news", Bytes::from("hello")).into_frame()
+        );
+    }
+
     #[test]
     fn parse_empty_array() {
         let buf = b"*0\r\n";
@@ -0,0 +1,172 @@
+//! Glob-style pattern matching, as used by Redis for `CONFIG GET` and other
+//! commands taking a pattern.
+//!
+//! Supported syntax:
+//!
+//! - `?` matches any single byte.
+//! - `*` matches any sequence of bytes, including an empty one.
+//! - `[abc]` matches one of the listed bytes, `[^abc]` any other byte, and
+//!   `[a-z]` a range. An unclosed class extends to the end of the pattern.
+//! - `\x` matches `x` literally.
+//!
+//! Matching is iterative. On a mismatch, only the most recent `*` is retried
+//! one byte further, as any earlier `*` can never do better. The work is thus
+//! bounded by the product of the pattern and string lengths, whatever the
+//! pattern, e.g. `a*a*a*b` against a long run of `a`.
+
+/// Returns whether `string` matches `pattern`, ignoring ASCII case if `nocase`
+/// is set.
+pub fn matches(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // Pattern index following the last `*` seen, and the string index that
+    // `*` is currently assumed to stop at.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while s < string.len() {
+        if p < pattern.len() {
+            if pattern[p] == b'*' {
+                p += 1;
+                backtrack = Some((p, s));
+                continue;
+            }
+
+            if let Some(len) = match_one(&pattern[p..], string[s], nocase) {
+                p += len;
+                s += 1;
+                continue;
+            }
+        }
+
+        // Let the last `*` absorb one more byte, if there is one.
+        match backtrack {
+            Some((star_p, star_s)) => {
+                p = star_p;
+                s = star_s + 1;
+                backtrack = Some((star_p, s));
+            }
+            None => return false,
+        }
+    }
+
+    // Trailing stars match the empty remainder.
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
+/// Matches `c` against the token at the start of `pattern`, which must not be
+/// `*`. Returns the length of the token on a match.
+fn match_one(pattern: &[u8], c: u8, nocase: bool) -> Option<usize> {
+    let eq = |a: u8, b: u8| a == b || (nocase && a.eq_ignore_ascii_case(&b));
+
+    match pattern[0] {
+        b'?' => Some(1),
+        b'\\' if pattern.len() > 1 => eq(pattern[1], c).then_some(2),
+        b'[' => {
+            let (len, matched) = match_class(&pattern[1..], c, nocase);
+            matched.then_some(len + 1)
+        }
+        literal => eq(literal, c).then_some(1),
+    }
+}
+
+/// Matches `c` against a character class, `pattern` starting right after the
+/// opening `[`. Returns the length of the class including the closing `]` and
+/// whether `c` matched.
+fn match_class(pattern: &[u8], c: u8, nocase: bool) -> (usize, bool) {
+    let fold = |b: u8| if nocase { b.to_ascii_lowercase() } else { b };
+    let c = fold(c);
+
+    let mut i = 0;
+    let negate = pattern.first() == Some(&b'^');
+    if negate {
+        i += 1;
+    }
+
+    let mut matched = false;
+    while i < pattern.len() && pattern[i] != b']' {
+        if pattern[i] == b'\\' && i + 1 < pattern.len() {
+            matched |= fold(pattern[i + 1]) == c;
+            i += 2;
+        } else if i + 2 < pattern.len() && pattern[i + 1] == b'-' && pattern[i + 2] != b']' {
+            let (mut start, mut end) = (fold(pattern[i]), fold(pattern[i + 2]));
+            if start > end {
+                std::mem::swap(&mut start, &mut end);
+            }
+            matched |= start <= c && c <= end;
+            i += 3;
+        } else {
+            matched |= fold(pattern[i]) == c;
+            i += 1;
+        }
+    }
+
+    // Include the closing `]`, unless the class runs to the end.
+    let len = (i + 1).min(pattern.len());
+    (len, matched != negate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, Instant};
+
+    fn glob(pattern: &str, string: &str) -> bool {
+        matches(pattern.as_bytes(), string.as_bytes(), false)
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(glob("*", ""));
+        assert!(glob("*", "anything"));
+        assert!(glob("h?llo", "hello"));
+        assert!(!glob("h?llo", "hllo"));
+        assert!(glob("h*llo", "hllo"));
+        assert!(glob("h*llo", "heeeello"));
+        assert!(glob("*llo", "hello"));
+        assert!(glob("he*", "hello"));
+        assert!(!glob("he*x", "hello"));
+        assert!(glob("a*b*c", "axxbyyc"));
+        assert!(!glob("a*b*c", "axxcyyb"));
+        assert!(glob("**", "x"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(glob("h[ae]llo", "hello"));
+        assert!(glob("h[ae]llo", "hallo"));
+        assert!(!glob("h[ae]llo", "hillo"));
+        assert!(glob("h[^e]llo", "hallo"));
+        assert!(!glob("h[^e]llo", "hello"));
+        assert!(glob("h[a-f]llo", "hello"));
+        assert!(glob("h[f-a]llo", "hello"));
+        assert!(!glob("h[a-d]llo", "hello"));
+        assert!(glob("h[a-]llo", "h-llo"));
+        // An unclosed class runs to the end of the pattern.
+        assert!(glob("h[ae", "he"));
+    }
+
+    #[test]
+    fn escapes() {
+        assert!(glob("h\\*llo", "h*llo"));
+        assert!(!glob("h\\*llo", "hello"));
+        assert!(glob("h[\\]]llo", "h]llo"));
+        assert!(glob("trailing\\", "trailing\\"));
+    }
+
+    #[test]
+    fn nocase() {
+        assert!(matches(b"HOT*", b"hotkeys-tracking", true));
+        assert!(matches(b"[A-Z]x", b"bX", true));
+        assert!(!matches(b"HOT*", b"hotkeys-tracking", false));
+    }
+
+    #[test]
+    fn pathological_pattern_completes_quickly() {
+        let pattern = "a*".repeat(32) + "b";
+        let string = "a".repeat(100_000);
+
+        let start = Instant::now();
+        assert!(!glob(&pattern, &string));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
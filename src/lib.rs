@@ -1,16 +1,25 @@
 pub mod clients;
 pub mod cmd;
+pub mod config;
 pub mod connection;
 pub mod db;
 pub mod frame;
+pub mod glob;
 pub mod server;
 pub mod shutdown;
+pub mod stream;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+mod workers;
 
 pub use connection::Connection;
 pub use frame::Frame;
 
 pub const DEFAULT_PORT: u16 = 6379;
 
+/// The mini-redis version reported to clients.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -1,19 +1,30 @@
 use std::future::Future;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{broadcast, mpsc, Semaphore};
-use tracing::error;
+use tracing::{debug, error};
 
 use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{self, Instant};
 
-use crate::cmd::Command;
-use crate::connection::Connection;
+use crate::cmd::{Command, CommandError};
+use crate::connection::{is_disconnect, Connection, ReadBuffers};
 use crate::db::{Db, DbDropGuard};
+use crate::frame::Frame;
 use crate::shutdown::Shutdown;
+use crate::workers::WorkerPool;
 
 struct Listener {
     listener: TcpListener,
     db_holder: DbDropGuard,
     limit_connections: Arc<Semaphore>,
+    /// bytes buffered across all connections
+    read_buffers: Arc<ReadBuffers>,
+    /// pool running commands away from connections, if enabled
+    workers: Option<WorkerPool>,
+    /// paces accepts when `max-accept-rate` is set
+    accept_rate: AcceptRate,
     /// to notify all handlers to shutdown
     notify_shutdown: broadcast::Sender<()>,
     shutdown_complete_tx: mpsc::Sender<()>,
@@ -29,18 +40,58 @@ impl Listener {
                 .await
                 .unwrap();
 
-            let socket = self.accept().await?;
+            let db = self.db_holder.db();
+
+            self.accept_rate.wait(db.settings().max_accept_rate).await;
+
+            let (socket, addr) = self.accept().await?;
+
+            let settings = db.settings();
+
+            let mut connection = Connection::new(socket)
+                .with_peer_addr(addr)
+                .with_max_bulk_len(settings.proto_max_bulk_len)
+                .with_frame_logging(settings.verbose_frame_logging)
+                .with_recovery_limit(settings.proto_recovery_limit);
+            let limit = settings.read_buffer_total_limit;
+            if limit > 0 {
+                connection = connection.with_read_budget(self.read_buffers.clone(), limit);
+            }
+
+            let session_deadline = match settings.max_session_duration {
+                0 => None,
+                secs => Some(Instant::now() + Duration::from_secs(secs)),
+            };
+
+            // A new pool replaces the current one when the number of workers
+            // changes. The old one stops once its connections are gone.
+            let workers = match settings.command_workers {
+                0 => None,
+                n => {
+                    if self.workers.as_ref().map(WorkerPool::workers) != Some(n) {
+                        self.workers = Some(WorkerPool::new(db.clone(), n));
+                    }
+                    self.workers.clone()
+                }
+            };
 
             let mut handler = Handler {
-                db: self.db_holder.db(),
-                connection: Connection::new(socket),
+                db,
+                connection,
+                workers,
+                session_deadline,
                 shutdown: Shutdown::new(self.notify_shutdown.subscribe()),
                 _shutdown_complete_tx: self.shutdown_complete_tx.clone(),
             };
 
             tokio::spawn(async move {
                 if let Err(err) = handler.run().await {
-                    error!(cause = ?err, "connection error");
+                    let peer = handler.connection.peer_addr();
+                    if is_disconnect(&err) {
+                        debug!(cause = ?err, %peer, "client disconnected");
+                    } else {
+                        error!(cause = ?err, %peer, "connection error");
+                    }
                 }
 
                 drop(permit);
@@ -48,9 +99,37 @@ impl Listener {
         }
     }
 
-    async fn accept(&mut self) -> crate::Result<TcpStream> {
-        let socket = self.listener.accept().await?.0;
-        Ok(socket)
+    async fn accept(&mut self) -> crate::Result<(TcpStream, SocketAddr)> {
+        let (socket, addr) = self.listener.accept().await?;
+        Ok((socket, addr))
+    }
+}
+
+/// A token bucket holding a single token: accepts are spaced at least
+/// `1 / rate` apart, without bursts.
+struct AcceptRate {
+    /// When the next token is available.
+    next: Instant,
+}
+
+impl AcceptRate {
+    fn new() -> AcceptRate {
+        AcceptRate {
+            next: Instant::now(),
+        }
+    }
+
+    /// Waits for a token at `rate` per second. `0` never waits.
+    async fn wait(&mut self, rate: u64) {
+        if rate == 0 {
+            return;
+        }
+
+        time::sleep_until(self.next).await;
+
+        // Counting from now rather than from `next`, an idle listener does not
+        // save up tokens for a burst.
+        self.next = Instant::now() + Duration::from_nanos(1_000_000_000 / rate);
     }
 }
 
@@ -64,6 +143,9 @@ pub async fn run(listener: TcpListener, shutdown: impl Future) {
         listener,
         db_holder: DbDropGuard::new(),
         limit_connections: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
+        read_buffers: Arc::new(ReadBuffers::new()),
+        workers: None,
+        accept_rate: AcceptRate::new(),
         notify_shutdown,
         shutdown_complete_tx,
     };
@@ -93,6 +175,10 @@ pub async fn run(listener: TcpListener, shutdown: impl Future) {
 struct Handler {
     db: Db,
     connection: Connection,
+    /// Pool running the commands that only need the database, if enabled.
+    workers: Option<WorkerPool>,
+    /// When the connection is closed regardless of activity, if ever.
+    session_deadline: Option<Instant>,
     shutdown: Shutdown,
     /// Not used directly. Instead, used when `Handler` is dropped.
     _shutdown_complete_tx: mpsc::Sender<()>,
@@ -106,6 +192,9 @@ impl Handler {
                 _ = self.shutdown.recv() => {
                     return Ok(());
                 }
+                _ = session_expired(self.session_deadline) => {
+                    return self.end_session().await;
+                }
             };
 
             let frame = match maybe_frame {
@@ -113,12 +202,51 @@ impl Handler {
                 None => return Ok(()),
             };
 
-            let cmd = Command::from_frame(frame)?;
+            let cmd = match Command::from_frame_or_reply(frame)? {
+                Ok(cmd) => cmd,
+                Err(err) => {
+                    self.connection.write_frame(&Frame::from(err)).await?;
+                    continue;
+                }
+            };
+
+            let cmd = match &self.workers {
+                Some(workers) => match cmd.into_db_command() {
+                    Ok(cmd) => {
+                        let response = workers.execute(cmd).await?;
+                        self.connection.write_frame(&response).await?;
+                        continue;
+                    }
+                    Err(cmd) => cmd,
+                },
+                None => cmd,
+            };
 
-            cmd.apply(&mut self.db, &mut self.connection, &mut self.shutdown)
-                .await?;
+            // Commands such as SUBSCRIBE or a blocking XREAD may run for as
+            // long as the connection lives, so the deadline is raced here too.
+            tokio::select! {
+                res = cmd.apply(&mut self.db, &mut self.connection, &mut self.shutdown) => res?,
+                _ = session_expired(self.session_deadline) => {
+                    return self.end_session().await;
+                }
+            }
         }
 
         Ok(())
     }
+
+    /// Tell the client its session is over before the connection is closed.
+    async fn end_session(&mut self) -> crate::Result<()> {
+        let response = Frame::from(CommandError::Err("session expired".to_string()));
+        self.connection.write_frame(&response).await?;
+        Ok(())
+    }
+}
+
+/// Completes at `deadline`, or never without one.
+async fn session_expired(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
 }
@@ -0,0 +1,261 @@
+//! The stream value type: an append-only log of field-value entries, each
+//! identified by a `<milliseconds>-<sequence>` ID.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Bound;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+
+use crate::cmd::CommandError;
+
+/// The ID of a stream entry. IDs are ordered by milliseconds, then sequence
+/// number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+/// The fields and values of a stream entry, in the order they were added.
+pub type Fields = Vec<(Bytes, Bytes)>;
+
+/// Stream entries with their IDs, in order.
+pub type Entries = Vec<(StreamId, Fields)>;
+
+/// The ID requested for a new entry by `XADD`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewId {
+    /// `*`: generated from the current time.
+    Auto,
+    /// `<ms>-*`: the sequence number is generated.
+    AutoSeq(u64),
+    /// `<ms>-<seq>`, or `<ms>` for a sequence number of `0`.
+    Explicit(StreamId),
+}
+
+/// A stream value.
+#[derive(Debug, Default)]
+pub struct Stream {
+    entries: BTreeMap<StreamId, Fields>,
+    /// The ID of the last entry ever added, which new IDs must be above.
+    last_id: StreamId,
+}
+
+impl StreamId {
+    pub const MIN: StreamId = StreamId { ms: 0, seq: 0 };
+    pub const MAX: StreamId = StreamId {
+        ms: u64::MAX,
+        seq: u64::MAX,
+    };
+
+    /// Parse the start of a range: `-` for the first entry, `<ms>` for the
+    /// first entry of that millisecond, or a full ID.
+    pub fn parse_start(s: &str) -> crate::Result<StreamId> {
+        match s {
+            "-" => Ok(StreamId::MIN),
+            _ => StreamId::parse(s, 0),
+        }
+    }
+
+    /// Parse the end of a range: `+` for the last entry, `<ms>` for the last
+    /// entry of that millisecond, or a full ID.
+    pub fn parse_end(s: &str) -> crate::Result<StreamId> {
+        match s {
+            "+" => Ok(StreamId::MAX),
+            _ => StreamId::parse(s, u64::MAX),
+        }
+    }
+
+    /// Parse `<ms>-<seq>`, or `<ms>` alone with `seq` as sequence number.
+    fn parse(s: &str, seq: u64) -> crate::Result<StreamId> {
+        let (ms, seq) = match s.split_once('-') {
+            Some((ms, seq)) => (ms.parse(), seq.parse()),
+            None => (s.parse(), Ok(seq)),
+        };
+
+        match (ms, seq) {
+            (Ok(ms), Ok(seq)) => Ok(StreamId { ms, seq }),
+            _ => Err(error(
+                "Invalid stream ID specified as stream command argument",
+            )),
+        }
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}-{}", self.ms, self.seq)
+    }
+}
+
+impl NewId {
+    pub fn parse(s: &str) -> crate::Result<NewId> {
+        if s == "*" {
+            return Ok(NewId::Auto);
+        }
+
+        if let Some(ms) = s.strip_suffix("-*") {
+            return match ms.parse() {
+                Ok(ms) => Ok(NewId::AutoSeq(ms)),
+                Err(_) => Err(error(
+                    "Invalid stream ID specified as stream command argument",
+                )),
+            };
+        }
+
+        Ok(NewId::Explicit(StreamId::parse(s, 0)?))
+    }
+}
+
+impl Stream {
+    pub fn new() -> Stream {
+        Stream::default()
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the ID of the last entry ever added, or `0-0`.
+    pub fn last_id(&self) -> StreamId {
+        self.last_id
+    }
+
+    /// Append an entry. The ID must be greater than that of every entry added
+    /// before. Returns the ID of the new entry.
+    pub fn add(&mut self, id: NewId, fields: Fields) -> crate::Result<StreamId> {
+        let last = self.last_id;
+
+        let id = match id {
+            NewId::Auto => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis() as u64);
+
+                // Keep IDs increasing even if the clock goes backwards.
+                if now > last.ms {
+                    StreamId { ms: now, seq: 0 }
+                } else {
+                    next_seq(last)?
+                }
+            }
+            NewId::AutoSeq(ms) if ms == last.ms => next_seq(last)?,
+            NewId::AutoSeq(ms) => StreamId { ms, seq: 0 },
+            NewId::Explicit(id) => id,
+        };
+
+        if id == StreamId::MIN {
+            return Err(error("The ID specified in XADD must be greater than 0-0"));
+        }
+        if id <= last {
+            return Err(error(
+                "The ID specified in XADD is equal or smaller than the target stream top item",
+            ));
+        }
+
+        self.entries.insert(id, fields);
+        self.last_id = id;
+
+        Ok(id)
+    }
+
+    /// Returns the entries with IDs between `start` and `end` inclusive, in
+    /// order, up to `count` of them.
+    pub fn range(&self, start: StreamId, end: StreamId, count: Option<usize>) -> Entries {
+        if start > end {
+            return Vec::new();
+        }
+
+        self.entries
+            .range((Bound::Included(start), Bound::Included(end)))
+            .take(count.unwrap_or(usize::MAX))
+            .map(|(id, fields)| (*id, fields.clone()))
+            .collect()
+    }
+
+    /// Returns the entries with IDs greater than `id`, in order, up to
+    /// `count` of them.
+    pub fn range_after(&self, id: StreamId, count: Option<usize>) -> Entries {
+        self.entries
+            .range((Bound::Excluded(id), Bound::Unbounded))
+            .take(count.unwrap_or(usize::MAX))
+            .map(|(id, fields)| (*id, fields.clone()))
+            .collect()
+    }
+}
+
+/// A generic error replied to the client.
+fn error(msg: &str) -> crate::Error {
+    CommandError::Err(msg.to_string()).into()
+}
+
+fn next_seq(last: StreamId) -> crate::Result<StreamId> {
+    match last.seq.checked_add(1) {
+        Some(seq) => Ok(StreamId { ms: last.ms, seq }),
+        None => Err(error("The stream has exhausted the last possible ID")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(ms: u64, seq: u64) -> StreamId {
+        StreamId { ms, seq }
+    }
+
+    #[test]
+    fn explicit_ids_must_increase() {
+        let mut stream = Stream::new();
+
+        assert_eq!(
+            id(5, 1),
+            stream.add(NewId::Explicit(id(5, 1)), vec![]).unwrap()
+        );
+        assert_eq!(id(5, 2), stream.add(NewId::AutoSeq(5), vec![]).unwrap());
+        assert_eq!(id(6, 0), stream.add(NewId::AutoSeq(6), vec![]).unwrap());
+
+        assert!(stream.add(NewId::Explicit(id(6, 0)), vec![]).is_err());
+        assert!(stream.add(NewId::Explicit(id(5, 9)), vec![]).is_err());
+        assert!(stream.add(NewId::AutoSeq(4), vec![]).is_err());
+        assert!(Stream::new()
+            .add(NewId::Explicit(id(0, 0)), vec![])
+            .is_err());
+
+        assert_eq!(3, stream.len());
+    }
+
+    #[test]
+    fn range_bounds() {
+        let mut stream = Stream::new();
+        for (ms, seq) in [(1, 0), (1, 1), (2, 0), (3, 0)] {
+            stream.add(NewId::Explicit(id(ms, seq)), vec![]).unwrap();
+        }
+
+        let ids = |start: &str, end: &str, count| -> Vec<StreamId> {
+            let start = StreamId::parse_start(start).unwrap();
+            let end = StreamId::parse_end(end).unwrap();
+            stream
+                .range(start, end, count)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect()
+        };
+
+        assert_eq!(
+            vec![id(1, 0), id(1, 1), id(2, 0), id(3, 0)],
+            ids("-", "+", None)
+        );
+        assert_eq!(vec![id(1, 0), id(1, 1)], ids("1", "1", None));
+        assert_eq!(vec![id(1, 1), id(2, 0)], ids("1-1", "2", None));
+        assert_eq!(vec![id(1, 0)], ids("-", "+", Some(1)));
+        assert!(ids("3", "1", None).is_empty());
+    }
+}
@@ -0,0 +1,216 @@
+//! Helpers for running a mini-redis server inside tests.
+
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::{server, Frame};
+
+/// A server listening on an ephemeral local port.
+///
+/// The server runs on a background task and is shut down when the
+/// `TestServer` is dropped. Use [`shutdown`](TestServer::shutdown) to also wait
+/// for every connection handler to complete.
+pub struct TestServer {
+    addr: SocketAddr,
+    /// Dropping the sender completes the shutdown future passed to
+    /// `server::run`.
+    shutdown_tx: Option<oneshot::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl TestServer {
+    /// Bind to an ephemeral port on 127.0.0.1 and start serving.
+    ///
+    /// The listener is bound before returning, so the server accepts
+    /// connections on [`addr`](TestServer::addr) right away.
+    pub async fn start() -> crate::Result<TestServer> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            server::run(listener, shutdown_rx).await;
+        });
+
+        Ok(TestServer {
+            addr,
+            shutdown_tx: Some(shutdown_tx),
+            handle: Some(handle),
+        })
+    }
+
+    /// The address clients should connect to.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Shut the server down and wait until all connection handlers are done.
+    ///
+    /// A panic of the server task is propagated to the caller.
+    pub async fn shutdown(mut self) {
+        drop(self.shutdown_tx.take());
+
+        if let Some(handle) = self.handle.take() {
+            if let Err(err) = handle.await {
+                if err.is_panic() {
+                    std::panic::resume_unwind(err.into_panic());
+                }
+            }
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        drop(self.shutdown_tx.take());
+    }
+}
+
+/// Build an array frame from a list of strings, integers and frames. Strings
+/// become bulk strings.
+///
+/// ```
+/// use mini_redis::{frame, Frame};
+/// use bytes::Bytes;
+///
+/// assert_eq!(
+///     Frame::Array(vec![
+///         Frame::Bulk(Bytes::from("subscribe")),
+///         Frame::Bulk(Bytes::from("news")),
+///         Frame::Integer(1),
+///     ]),
+///     frame!["subscribe", "news", 1],
+/// );
+/// ```
+#[macro_export]
+macro_rules! frame {
+    ($($item:expr),* $(,)?) => {
+        $crate::Frame::Array(vec![$($crate::testing::ToFrame::to_frame($item)),*])
+    };
+}
+
+/// Conversion of the items accepted by [`frame!`].
+pub trait ToFrame {
+    fn to_frame(self) -> Frame;
+}
+
+impl ToFrame for Frame {
+    fn to_frame(self) -> Frame {
+        self
+    }
+}
+
+impl ToFrame for &str {
+    fn to_frame(self) -> Frame {
+        Frame::Bulk(Bytes::copy_from_slice(self.as_bytes()))
+    }
+}
+
+impl ToFrame for &String {
+    fn to_frame(self) -> Frame {
+        self.as_str().to_frame()
+    }
+}
+
+impl ToFrame for String {
+    fn to_frame(self) -> Frame {
+        Frame::Bulk(Bytes::from(self))
+    }
+}
+
+impl ToFrame for Bytes {
+    fn to_frame(self) -> Frame {
+        Frame::Bulk(self)
+    }
+}
+
+impl ToFrame for i64 {
+    fn to_frame(self) -> Frame {
+        Frame::Integer(self)
+    }
+}
+
+impl ToFrame for i32 {
+    fn to_frame(self) -> Frame {
+        Frame::Integer(self.into())
+    }
+}
+
+impl ToFrame for usize {
+    fn to_frame(self) -> Frame {
+        Frame::Integer(self as i64)
+    }
+}
+
+/// Assert that two frames are equal. On mismatch, the elements of arrays are
+/// listed one per line, with the differing ones marked.
+#[track_caller]
+pub fn assert_frame_eq(expected: &Frame, actual: &Frame) {
+    if expected == actual {
+        return;
+    }
+
+    let mut msg = String::from("frames differ\n");
+    match (expected, actual) {
+        (Frame::Array(expected), Frame::Array(actual)) => {
+            for i in 0..expected.len().max(actual.len()) {
+                let (left, right) = (expected.get(i), actual.get(i));
+                let marker = if left == right { " " } else { ">" };
+                msg.push_str(&format!(
+                    "{} [{}] expected: {}, actual: {}\n",
+                    marker,
+                    i,
+                    describe(left),
+                    describe(right)
+                ));
+            }
+        }
+        _ => msg.push_str(&format!(
+            "  expected: {:?}\n    actual: {:?}\n",
+            expected, actual
+        )),
+    }
+
+    panic!("{}", msg);
+}
+
+fn describe(frame: Option<&Frame>) -> String {
+    match frame {
+        Some(frame) => format!("{:?}", frame),
+        None => "(missing)".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_macro_matches_hand_built_frame() {
+        let channel = "news".to_string();
+
+        let mut expected = Frame::array();
+        expected.push_bulk(Bytes::from_static(b"subscribe"));
+        expected.push_bulk(Bytes::from_static(b"news"));
+        expected.push_int(1);
+        expected.push_bulk(Bytes::from_static(b"raw"));
+
+        assert_frame_eq(
+            &expected,
+            &frame!["subscribe", channel, 1, Bytes::from_static(b"raw")],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "> [2] expected: Integer(1), actual: Integer(2)")]
+    fn assert_frame_eq_marks_differing_elements() {
+        assert_frame_eq(
+            &frame!["subscribe", "news", 1],
+            &frame!["subscribe", "news", 2],
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! A fixed pool of tasks running commands that only need the database, fed by
+//! the connections through a bounded queue.
+//!
+//! Connection tasks then only read requests and write responses. Each command
+//! carries a `oneshot` sender, on which the worker sends the response back to
+//! the connection that submitted it.
+
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use crate::cmd::DbCommand;
+use crate::db::Db;
+use crate::frame::Frame;
+
+/// Number of queued commands per worker above which connections wait to
+/// submit more.
+const QUEUE_LEN_PER_WORKER: usize = 64;
+
+/// Handle to submit commands to the pool. The workers stop once every handle
+/// has been dropped and the queue is drained.
+#[derive(Clone)]
+pub(crate) struct WorkerPool {
+    jobs: mpsc::Sender<Job>,
+    workers: usize,
+}
+
+struct Job {
+    cmd: DbCommand,
+    respond: oneshot::Sender<Frame>,
+}
+
+impl WorkerPool {
+    /// Spawn `workers` tasks running commands against `db`.
+    pub(crate) fn new(db: Db, workers: usize) -> WorkerPool {
+        let (tx, rx) = mpsc::channel(workers.max(1) * QUEUE_LEN_PER_WORKER);
+        let rx = Arc::new(Mutex::new(rx));
+
+        for _ in 0..workers {
+            tokio::spawn(run_worker(db.clone(), rx.clone()));
+        }
+
+        WorkerPool { jobs: tx, workers }
+    }
+
+    /// Returns the number of workers.
+    pub(crate) fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Queue `cmd` and wait for its response.
+    pub(crate) async fn execute(&self, cmd: DbCommand) -> crate::Result<Frame> {
+        let (tx, rx) = oneshot::channel();
+        let job = Job { cmd, respond: tx };
+
+        if self.jobs.send(job).await.is_err() {
+            return Err("command workers are gone".into());
+        }
+
+        Ok(rx.await?)
+    }
+}
+
+async fn run_worker(db: Db, jobs: Arc<Mutex<mpsc::Receiver<Job>>>) {
+    loop {
+        // The lock is released as soon as a job is received, letting another
+        // worker wait for the next one.
+        let job = jobs.lock().await.recv().await;
+        let job = match job {
+            Some(job) => job,
+            None => return,
+        };
+
+        let response = job.cmd.execute(&db).unwrap_or_else(Frame::from);
+
+        // The connection may have gone away in the meantime.
+        let _ = job.respond.send(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cmd::Command;
+    use bytes::Bytes;
+
+    fn db_command(args: &[&str]) -> DbCommand {
+        let frame = Frame::Array(
+            args.iter()
+                .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg.as_bytes())))
+                .collect(),
+        );
+        match Command::from_frame(frame).unwrap().into_db_command() {
+            Ok(cmd) => cmd,
+            Err(_) => panic!("not a db command: {:?}", args),
+        }
+    }
+
+    #[tokio::test]
+    async fn responses_are_routed_to_their_submitter() {
+        let db = Db::new();
+        let pool = WorkerPool::new(db.clone(), 4);
+
+        let mut tasks = Vec::new();
+        for i in 0..100 {
+            let pool = pool.clone();
+            tasks.push(tokio::spawn(async move {
+                let key = format!("key:{}", i);
+                let value = i.to_string();
+
+                let response = pool.execute(db_command(&["SET", &key, &value])).await;
+                assert_eq!(Frame::Simple("OK".to_string()), response.unwrap());
+
+                let response = pool.execute(db_command(&["GET", &key])).await;
+                assert_eq!(Frame::Bulk(Bytes::from(value)), response.unwrap());
+            }));
+        }
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+    }
+}
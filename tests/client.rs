@@ -0,0 +1,33 @@
+use mini_redis::{clients::Client, testing::TestServer};
+
+use bytes::Bytes;
+use std::string::FromUtf8Error;
+
+/// `get_string` decodes UTF-8 values, returns `None` for missing keys and
+/// fails on values that are not UTF-8.
+#[tokio::test]
+async fn get_string_decodes_utf8() {
+    let server = TestServer::start().await.unwrap();
+    let mut client = Client::connect(server.addr()).await.unwrap();
+
+    client.set("text", Bytes::from("héllo")).await.unwrap();
+    assert_eq!(
+        Some("héllo".to_string()),
+        client.get_string("text").await.unwrap()
+    );
+
+    assert_eq!(None, client.get_string("missing").await.unwrap());
+
+    client
+        .set("binary", Bytes::from_static(&[0xff, 0xfe]))
+        .await
+        .unwrap();
+    let err = client.get_string("binary").await.unwrap_err();
+    assert!(err.downcast_ref::<FromUtf8Error>().is_some());
+
+    // The connection is still usable after a decoding error.
+    assert_eq!(
+        Some(Bytes::from_static(&[0xff, 0xfe])),
+        client.get("binary").await.unwrap()
+    );
+}
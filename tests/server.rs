@@ -0,0 +1,1198 @@
+use mini_redis::testing::{assert_frame_eq, TestServer};
+use mini_redis::{frame, Connection, Frame};
+
+use bytes::Bytes;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// A subscribe acknowledgment is always written before any message published
+/// on the channel, even when the publish races with the subscription.
+#[tokio::test]
+async fn subscribe_ack_precedes_message() {
+    let server = TestServer::start().await.unwrap();
+    let addr = server.addr();
+
+    let mut subscriber = TcpStream::connect(addr).await.unwrap();
+    let mut publisher = TcpStream::connect(addr).await.unwrap();
+
+    // Subscribe without waiting for the acknowledgment.
+    subscriber
+        .write_all(b"*2\r\n$9\r\nsubscribe\r\n$5\r\nhello\r\n")
+        .await
+        .unwrap();
+
+    // Publish until the server reports the subscriber as registered. The
+    // message then races with the acknowledgment being written.
+    loop {
+        publisher
+            .write_all(b"*3\r\n$7\r\npublish\r\n$5\r\nhello\r\n$5\r\nworld\r\n")
+            .await
+            .unwrap();
+
+        let mut response = [0; 4];
+        publisher.read_exact(&mut response).await.unwrap();
+        if &response == b":1\r\n" {
+            break;
+        }
+        assert_eq!(b":0\r\n", &response);
+    }
+
+    let mut response = [0; 34];
+    subscriber.read_exact(&mut response).await.unwrap();
+    assert_eq!(
+        &b"*3\r\n$9\r\nsubscribe\r\n$5\r\nhello\r\n:1\r\n"[..],
+        &response[..]
+    );
+
+    let mut response = [0; 39];
+    subscriber.read_exact(&mut response).await.unwrap();
+    assert_eq!(
+        &b"*3\r\n$7\r\nmessage\r\n$5\r\nhello\r\n$5\r\nworld\r\n"[..],
+        &response[..]
+    );
+}
+
+/// `INFO replication` reports the role declared with `REPLICAOF`.
+#[tokio::test]
+async fn replicaof_sets_reported_role() {
+    let server = TestServer::start().await.unwrap();
+    let addr = server.addr();
+    let mut conn = Connection::new(TcpStream::connect(addr).await.unwrap());
+
+    assert_eq!(
+        Frame::Simple("OK".to_string()),
+        request(&mut conn, &["REPLICAOF", "127.0.0.1", "6380"]).await
+    );
+    let info = request(&mut conn, &["INFO", "replication"])
+        .await
+        .to_string();
+    assert!(info.contains("role:slave\r\n"));
+    assert!(info.contains("master_host:127.0.0.1\r\n"));
+    assert!(info.contains("master_port:6380\r\n"));
+
+    assert_eq!(
+        Frame::Simple("OK".to_string()),
+        request(&mut conn, &["REPLICAOF", "NO", "ONE"]).await
+    );
+    let info = request(&mut conn, &["INFO", "replication"])
+        .await
+        .to_string();
+    assert!(info.contains("role:master\r\n"));
+    assert!(!info.contains("master_host"));
+
+    // A port that is not a valid integer is replied to with an error, and the
+    // connection stays open.
+    let not_integer = Frame::Error("ERR value is not an integer or out of range".to_string());
+    for port in ["abc", "70000"] {
+        assert_eq!(
+            not_integer,
+            request(&mut conn, &["REPLICAOF", "127.0.0.1", port]).await
+        );
+    }
+    let info = request(&mut conn, &["INFO", "replication"])
+        .await
+        .to_string();
+    assert!(info.contains("role:master\r\n"));
+}
+
+/// `LOLWUT` reports the server version, with or without a `VERSION` argument.
+#[tokio::test]
+async fn lolwut_reports_version() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    let art = request(&mut conn, &["LOLWUT"]).await.to_string();
+    assert!(art.contains(mini_redis::VERSION));
+
+    let art = request(&mut conn, &["LOLWUT", "VERSION", "5"])
+        .await
+        .to_string();
+    assert!(art.contains(mini_redis::VERSION));
+}
+
+/// With `hotkeys-tracking` enabled, the most read key ranks first.
+#[tokio::test]
+async fn hotkeys_ranks_most_accessed_key_first() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    assert_eq!(
+        Frame::Simple("OK".to_string()),
+        request(&mut conn, &["CONFIG", "SET", "hotkeys-tracking", "yes"]).await
+    );
+
+    request(&mut conn, &["SET", "cold", "1"]).await;
+    request(&mut conn, &["SET", "hot", "2"]).await;
+    for _ in 0..3 {
+        request(&mut conn, &["GET", "hot"]).await;
+    }
+
+    assert_frame_eq(
+        &frame!["hot", 4, "cold", 1],
+        &request(&mut conn, &["HOTKEYS"]).await,
+    );
+    assert_frame_eq(
+        &frame!["hot", 4],
+        &request(&mut conn, &["HOTKEYS", "1"]).await,
+    );
+}
+
+/// `DEBUG EXPIRATIONS` lists keys with a TTL, soonest expiration first.
+#[tokio::test]
+async fn debug_expirations_lists_soonest_first() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    request(&mut conn, &["SET", "late", "1", "PX", "30000"]).await;
+    request(&mut conn, &["SET", "early", "2", "PX", "10000"]).await;
+    request(&mut conn, &["SET", "middle", "3", "PX", "20000"]).await;
+    request(&mut conn, &["SET", "persistent", "4"]).await;
+
+    let entries = match request(&mut conn, &["DEBUG", "EXPIRATIONS"]).await {
+        Frame::Array(entries) => entries,
+        frame => panic!("expected array, got {:?}", frame),
+    };
+    assert_eq!(6, entries.len());
+
+    let expected = [("early", 10000), ("middle", 20000), ("late", 30000)];
+    for (pair, (key, ttl)) in entries.chunks(2).zip(expected) {
+        assert_eq!(Frame::Bulk(Bytes::from(key)), pair[0]);
+        match pair[1] {
+            Frame::Integer(ms) => assert!(ms > ttl - 5000 && ms <= ttl, "{} ms", ms),
+            ref frame => panic!("expected integer, got {:?}", frame),
+        }
+    }
+
+    assert_eq!(
+        2,
+        match request(&mut conn, &["DEBUG", "EXPIRATIONS", "1"]).await {
+            Frame::Array(entries) => entries.len(),
+            frame => panic!("expected array, got {:?}", frame),
+        }
+    );
+}
+
+/// A subscriber that does not read its messages is disconnected once it falls
+/// more messages behind than the pub/sub output buffer limit.
+#[tokio::test]
+async fn slow_subscriber_is_disconnected() {
+    let server = TestServer::start().await.unwrap();
+    let mut publisher = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    assert_eq!(
+        Frame::Simple("OK".to_string()),
+        request(
+            &mut publisher,
+            &["CONFIG", "SET", "client-output-buffer-limit", "pubsub 10"]
+        )
+        .await
+    );
+
+    let mut subscriber = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    request(&mut subscriber, &["SUBSCRIBE", "hello"]).await;
+
+    // Large messages quickly fill the socket buffers, after which the server
+    // can no longer keep up with the channel.
+    let message = "x".repeat(64 * 1024);
+    let mut disconnected = false;
+    for _ in 0..1000 {
+        if request(&mut publisher, &["PUBLISH", "hello", &message]).await == Frame::Integer(0) {
+            disconnected = true;
+            break;
+        }
+    }
+    assert!(disconnected);
+}
+
+/// `PEXPIRETIME` reports the Unix time set with `PXAT`, `EXPIRETIME` the same
+/// time in seconds.
+#[tokio::test]
+async fn expiretime_reports_absolute_expiration() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let at = (now + Duration::from_secs(100)).as_millis() as i64;
+    request(&mut conn, &["SET", "foo", "bar", "PXAT", &at.to_string()]).await;
+    request(&mut conn, &["SET", "persistent", "bar"]).await;
+
+    // The conversion between clocks may be off by a rounding error.
+    match request(&mut conn, &["PEXPIRETIME", "foo"]).await {
+        Frame::Integer(ms) => assert!((ms - at).abs() <= 1, "{} != {}", ms, at),
+        frame => panic!("expected integer, got {:?}", frame),
+    }
+    match request(&mut conn, &["EXPIRETIME", "foo"]).await {
+        Frame::Integer(secs) => assert!((secs - at / 1000).abs() <= 1),
+        frame => panic!("expected integer, got {:?}", frame),
+    }
+
+    assert_eq!(
+        Frame::Integer(-1),
+        request(&mut conn, &["PEXPIRETIME", "persistent"]).await
+    );
+    assert_eq!(
+        Frame::Integer(-2),
+        request(&mut conn, &["PEXPIRETIME", "missing"]).await
+    );
+}
+
+/// An empty command array is answered with an error and the connection stays
+/// usable.
+#[tokio::test]
+async fn empty_command_keeps_connection() {
+    let server = TestServer::start().await.unwrap();
+    let mut stream = TcpStream::connect(server.addr()).await.unwrap();
+
+    stream
+        .write_all(b"*0\r\n*1\r\n$4\r\nPING\r\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 27];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(&b"-ERR empty command\r\n+PONG\r\n"[..], &response[..]);
+}
+
+/// Subscribing past the per-connection limit fails, but the subscriptions
+/// made before keep receiving messages.
+#[tokio::test]
+async fn subscribe_past_limit_keeps_prior_subscriptions() {
+    let server = TestServer::start().await.unwrap();
+    let mut publisher = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    assert_eq!(
+        Frame::Simple("OK".to_string()),
+        request(
+            &mut publisher,
+            &["CONFIG", "SET", "max-subscriptions-per-client", "2"]
+        )
+        .await
+    );
+
+    let mut subscriber = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    request(&mut subscriber, &["SUBSCRIBE", "one", "two"]).await;
+    subscriber.read_frame().await.unwrap().unwrap();
+
+    assert_eq!(
+        Frame::Error("ERR max number of subscriptions reached".to_string()),
+        request(&mut subscriber, &["SUBSCRIBE", "three"]).await
+    );
+
+    assert_eq!(
+        Frame::Integer(1),
+        request(&mut publisher, &["PUBLISH", "one", "hello"]).await
+    );
+    assert_frame_eq(
+        &frame!["message", "one", "hello"],
+        &subscriber.read_frame().await.unwrap().unwrap(),
+    );
+}
+
+/// The error for an unknown command echoes its first arguments.
+#[tokio::test]
+async fn unknown_command_echoes_args() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    assert_eq!(
+        Frame::Error(
+            "ERR unknown command 'FOO', with args beginning with: 'bar' 'baz' ".to_string()
+        ),
+        request(&mut conn, &["FOO", "bar", "baz"]).await
+    );
+
+    // Arguments past the first 128 bytes are left out.
+    let long = "x".repeat(200);
+    let expected = format!(
+        "ERR unknown command 'FOO', with args beginning with: '{}' ",
+        &long[..128]
+    );
+    assert_eq!(
+        Frame::Error(expected),
+        request(&mut conn, &["FOO", &long, "baz"]).await
+    );
+}
+
+/// A connection is closed at the end of its maximum session duration, even
+/// while it keeps sending commands.
+#[tokio::test]
+async fn active_connection_closed_at_session_deadline() {
+    let server = TestServer::start().await.unwrap();
+
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    request(&mut conn, &["CONFIG", "SET", "max-session-duration", "1"]).await;
+
+    let start = Instant::now();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    loop {
+        match request(&mut conn, &["PING"]).await {
+            Frame::Simple(pong) => assert_eq!("PONG", pong),
+            frame => {
+                assert_eq!(Frame::Error("ERR session expired".to_string()), frame);
+                break;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    assert!(start.elapsed() >= Duration::from_secs(1));
+    assert_eq!(None, conn.read_frame().await.unwrap());
+}
+
+/// Subscribed connections and blocked reads are closed at the session
+/// deadline too, while they wait.
+#[tokio::test]
+async fn waiting_connection_closed_at_session_deadline() {
+    let server = TestServer::start().await.unwrap();
+
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    request(&mut conn, &["CONFIG", "SET", "max-session-duration", "1"]).await;
+
+    let mut subscriber = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    request(&mut subscriber, &["SUBSCRIBE", "hello"]).await;
+
+    let mut reader = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    reader
+        .write_frame(&frame!["XREAD", "BLOCK", "0", "STREAMS", "s", "$"])
+        .await
+        .unwrap();
+
+    let expired = Frame::Error("ERR session expired".to_string());
+    for conn in [&mut subscriber, &mut reader] {
+        let frame = tokio::time::timeout(Duration::from_secs(5), conn.read_frame())
+            .await
+            .expect("connection outlived its session");
+        assert_eq!(Some(expired.clone()), frame.unwrap());
+        assert_eq!(None, conn.read_frame().await.unwrap());
+    }
+}
+
+/// Shutting down while connections are being accepted and served completes
+/// once every handler is done, after which no connection is accepted.
+#[tokio::test]
+async fn shutdown_while_accepting_connections() {
+    let server = TestServer::start().await.unwrap();
+    let addr = server.addr();
+
+    let clients: Vec<_> = (0..16)
+        .map(|_| {
+            tokio::spawn(async move {
+                // Connect and ping until the server goes away.
+                while let Ok(stream) = TcpStream::connect(addr).await {
+                    let mut conn = Connection::new(stream);
+                    let ping = frame!["PING"];
+                    if conn.write_frame(&ping).await.is_err() {
+                        break;
+                    }
+                    match conn.read_frame().await {
+                        Ok(Some(frame)) => assert_eq!(Frame::Simple("PONG".to_string()), frame),
+                        _ => break,
+                    }
+                }
+            })
+        })
+        .collect();
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    tokio::time::timeout(Duration::from_secs(5), server.shutdown())
+        .await
+        .expect("shutdown did not complete");
+
+    assert!(TcpStream::connect(addr).await.is_err());
+
+    for client in clients {
+        client.await.unwrap();
+    }
+}
+
+/// `DEBUG STRINGMATCH-LEN` reports whether a string matches a glob pattern.
+#[tokio::test]
+async fn debug_stringmatch() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    assert_eq!(
+        Frame::Integer(1),
+        request(
+            &mut conn,
+            &["DEBUG", "STRINGMATCH-LEN", "h?llo*", "hello world"]
+        )
+        .await
+    );
+    assert_eq!(
+        Frame::Integer(0),
+        request(
+            &mut conn,
+            &["DEBUG", "STRINGMATCH-LEN", "h[^e]llo", "hello"]
+        )
+        .await
+    );
+}
+
+/// Entries appended with generated IDs are read back in order, and stream
+/// commands reject keys holding strings.
+#[tokio::test]
+async fn xadd_and_xrange() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    let first = match request(&mut conn, &["XADD", "s", "*", "a", "1"]).await {
+        Frame::Bulk(id) => String::from_utf8(id.to_vec()).unwrap(),
+        frame => panic!("unexpected frame: {:?}", frame),
+    };
+    let second = match request(&mut conn, &["XADD", "s", "*", "b", "2", "c", "3"]).await {
+        Frame::Bulk(id) => String::from_utf8(id.to_vec()).unwrap(),
+        frame => panic!("unexpected frame: {:?}", frame),
+    };
+
+    assert_frame_eq(
+        &frame![
+            frame![&first, frame!["a", "1"]],
+            frame![&second, frame!["b", "2", "c", "3"]]
+        ],
+        &request(&mut conn, &["XRANGE", "s", "-", "+"]).await,
+    );
+    assert_frame_eq(
+        &frame![frame![&first, frame!["a", "1"]]],
+        &request(&mut conn, &["XRANGE", "s", "-", "+", "COUNT", "1"]).await,
+    );
+    assert_eq!(Frame::Integer(2), request(&mut conn, &["XLEN", "s"]).await);
+
+    // IDs must keep increasing.
+    assert!(matches!(
+        request(&mut conn, &["XADD", "s", &first, "d", "4"]).await,
+        Frame::Error(_)
+    ));
+
+    request(&mut conn, &["SET", "str", "value"]).await;
+    assert_eq!(
+        Frame::Error("WRONGTYPE Operation against a key holding the wrong kind of value".into()),
+        request(&mut conn, &["XADD", "str", "*", "a", "1"]).await
+    );
+    assert!(matches!(
+        request(&mut conn, &["GET", "s"]).await,
+        Frame::Error(_)
+    ));
+}
+
+/// A client blocked in `XREAD` is woken by an entry added from another
+/// connection. `$` only waits for entries added after the call.
+#[tokio::test]
+async fn xread_block_wakes_on_xadd() {
+    let server = TestServer::start().await.unwrap();
+    let mut reader = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    let mut writer = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    request(&mut writer, &["XADD", "s", "1-0", "a", "1"]).await;
+
+    // `$` skips the existing entry.
+    assert_eq!(
+        Frame::NullArray,
+        request(&mut reader, &["XREAD", "BLOCK", "50", "STREAMS", "s", "$"]).await
+    );
+    assert_eq!(
+        Frame::NullArray,
+        request(&mut reader, &["XREAD", "STREAMS", "s", "1-0"]).await
+    );
+
+    // Send without waiting for the response, which only comes once an entry
+    // is added.
+    let frame = frame!["XREAD", "BLOCK", "0", "STREAMS", "s", "1-0"];
+    reader.write_frame(&frame).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    request(&mut writer, &["XADD", "s", "2-0", "b", "2"]).await;
+    assert_frame_eq(
+        &frame![frame!["s", frame![frame!["2-0", frame!["b", "2"]]]]],
+        &reader.read_frame().await.unwrap().unwrap(),
+    );
+}
+
+/// With command workers enabled, commands run on the pool and the others on
+/// the connection answer in request order.
+#[tokio::test]
+async fn command_workers_serve_new_connections() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    request(&mut conn, &["CONFIG", "SET", "command-workers", "2"]).await;
+
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    assert_eq!(
+        Frame::Simple("OK".to_string()),
+        request(&mut conn, &["SET", "foo", "bar"]).await
+    );
+    assert_eq!(
+        Frame::Simple("PONG".to_string()),
+        request(&mut conn, &["PING"]).await
+    );
+    assert_eq!(
+        Frame::Bulk(Bytes::from("bar")),
+        request(&mut conn, &["GET", "foo"]).await
+    );
+}
+
+/// `CLUSTER` reports a single node with cluster support disabled.
+#[tokio::test]
+async fn cluster_reports_single_node() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    let info = match request(&mut conn, &["CLUSTER", "INFO"]).await {
+        Frame::Bulk(info) => String::from_utf8(info.to_vec()).unwrap(),
+        frame => panic!("unexpected frame: {:?}", frame),
+    };
+    assert!(info.contains("cluster_enabled:0\r\n"), "{}", info);
+
+    assert_eq!(
+        Frame::Array(vec![]),
+        request(&mut conn, &["CLUSTER", "SLOTS"]).await
+    );
+
+    let id = request(&mut conn, &["CLUSTER", "MYID"]).await;
+    match &id {
+        Frame::Bulk(id) => assert_eq!(40, id.len()),
+        frame => panic!("unexpected frame: {:?}", frame),
+    }
+    assert_eq!(id, request(&mut conn, &["CLUSTER", "MYID"]).await);
+}
+
+/// A subscribed connection may only run the commands allowed in subscribe
+/// mode.
+#[tokio::test]
+async fn subscribe_mode_allows_flagged_commands() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    request(&mut conn, &["SUBSCRIBE", "hello"]).await;
+
+    assert_frame_eq(&frame!["pong", ""], &request(&mut conn, &["PING"]).await);
+    assert_frame_eq(
+        &frame!["pong", "hi"],
+        &request(&mut conn, &["PING", "hi"]).await,
+    );
+    assert_eq!(
+        Frame::Error(
+            "ERR Can't execute 'get': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context"
+                .to_string()
+        ),
+        request(&mut conn, &["GET", "foo"]).await
+    );
+    assert_frame_eq(
+        &frame!["unsubscribe", "hello", 0],
+        &request(&mut conn, &["UNSUBSCRIBE"]).await,
+    );
+}
+
+/// An empty command array is answered with an error in subscribe mode too,
+/// and the connection keeps receiving messages.
+#[tokio::test]
+async fn empty_command_keeps_subscribed_connection() {
+    let server = TestServer::start().await.unwrap();
+    let mut subscriber = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    request(&mut subscriber, &["SUBSCRIBE", "hello"]).await;
+
+    assert_eq!(
+        Frame::Error("ERR empty command".to_string()),
+        request(&mut subscriber, &[]).await
+    );
+
+    let mut publisher = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    request(&mut publisher, &["PUBLISH", "hello", "world"]).await;
+    assert_frame_eq(
+        &frame!["message", "hello", "world"],
+        &subscriber.read_frame().await.unwrap().unwrap(),
+    );
+}
+
+/// Shutting down promptly ends the handlers of idle, subscribed and blocked
+/// connections, and closes their connections.
+#[tokio::test]
+async fn shutdown_ends_idle_and_long_running_handlers() {
+    let server = TestServer::start().await.unwrap();
+
+    let mut idle = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    request(&mut idle, &["PING"]).await;
+
+    let mut subscribed = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    request(&mut subscribed, &["SUBSCRIBE", "hello"]).await;
+
+    let mut blocked = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    let xread = frame!["XREAD", "BLOCK", "0", "STREAMS", "s", "$"];
+    blocked.write_frame(&xread).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    tokio::time::timeout(Duration::from_millis(500), server.shutdown())
+        .await
+        .expect("handlers did not complete");
+
+    for mut conn in [idle, subscribed, blocked] {
+        let closed = tokio::time::timeout(Duration::from_millis(500), conn.read_frame())
+            .await
+            .expect("connection was not closed");
+        assert!(matches!(closed, Ok(None) | Err(_)), "{:?}", closed);
+    }
+}
+
+/// Shutting down does not wait for a subscriber that stopped reading, on which
+/// a message write is blocked.
+#[tokio::test]
+async fn shutdown_ends_handler_blocked_writing_to_subscriber() {
+    let server = TestServer::start().await.unwrap();
+
+    let mut subscriber = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    request(&mut subscriber, &["SUBSCRIBE", "hello"]).await;
+
+    // Publish until the socket buffers are full and the write of a message
+    // blocks. The subscriber never reads.
+    let mut publisher = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    let message = "x".repeat(64 * 1024);
+    for _ in 0..200 {
+        request(&mut publisher, &["PUBLISH", "hello", &message]).await;
+    }
+
+    tokio::time::timeout(Duration::from_millis(500), server.shutdown())
+        .await
+        .expect("handler did not complete");
+}
+
+/// With `max-accept-rate` set, a burst of connections is accepted evenly
+/// spaced rather than all at once.
+#[tokio::test]
+async fn max_accept_rate_spaces_out_burst() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    request(&mut conn, &["CONFIG", "SET", "max-accept-rate", "20"]).await;
+
+    let addr = server.addr();
+    let start = Instant::now();
+    let clients = (0..10).map(|_| {
+        tokio::spawn(async move {
+            let mut conn = Connection::new(TcpStream::connect(addr).await.unwrap());
+            request(&mut conn, &["PING"]).await
+        })
+    });
+    for client in clients.collect::<Vec<_>>() {
+        assert_eq!(Frame::Simple("PONG".to_string()), client.await.unwrap());
+    }
+
+    // The listener may already be waiting to accept with no limit, and the
+    // first limited accept finds a token: the other eight wait 50ms each.
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(400), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+}
+
+/// `EXISTS` counts every existing key given, including repeats.
+#[tokio::test]
+async fn exists_counts_repeated_keys() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    request(&mut conn, &["SET", "foo", "bar"]).await;
+    request(&mut conn, &["XADD", "events", "*", "field", "value"]).await;
+
+    assert_eq!(
+        Frame::Integer(2),
+        request(&mut conn, &["EXISTS", "foo", "foo"]).await
+    );
+    assert_eq!(
+        Frame::Integer(2),
+        request(&mut conn, &["EXISTS", "foo", "missing", "events"]).await
+    );
+    assert_eq!(
+        Frame::Integer(0),
+        request(&mut conn, &["EXISTS", "missing"]).await
+    );
+
+    // Without keys, the error is replied and the connection stays open.
+    assert_eq!(
+        Frame::Error("ERR wrong number of arguments for 'exists' command".to_string()),
+        request(&mut conn, &["EXISTS"]).await
+    );
+    assert_eq!(
+        Frame::Integer(1),
+        request(&mut conn, &["EXISTS", "foo"]).await
+    );
+}
+
+/// `INCR` and `DECR` count from `0` for a missing key, and reject values
+/// that are not integers and results that overflow.
+#[tokio::test]
+async fn incr_and_decr() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    assert_eq!(
+        Frame::Integer(1),
+        request(&mut conn, &["INCR", "counter"]).await
+    );
+    assert_eq!(
+        Frame::Integer(2),
+        request(&mut conn, &["INCR", "counter"]).await
+    );
+    assert_eq!(
+        Frame::Integer(-1),
+        request(&mut conn, &["DECR", "missing"]).await
+    );
+
+    request(&mut conn, &["SET", "counter", "41"]).await;
+    assert_eq!(
+        Frame::Integer(42),
+        request(&mut conn, &["INCR", "counter"]).await
+    );
+    assert_eq!(
+        Frame::Bulk(Bytes::from("42")),
+        request(&mut conn, &["GET", "counter"]).await
+    );
+
+    let not_an_integer = Frame::Error("ERR value is not an integer or out of range".to_string());
+    request(&mut conn, &["SET", "name", "foo"]).await;
+    assert_eq!(not_an_integer, request(&mut conn, &["INCR", "name"]).await);
+
+    let max = i64::MAX.to_string();
+    request(&mut conn, &["SET", "max", &max]).await;
+    assert_eq!(not_an_integer, request(&mut conn, &["INCR", "max"]).await);
+    assert_eq!(
+        Frame::Bulk(Bytes::from(max)),
+        request(&mut conn, &["GET", "max"]).await
+    );
+}
+
+/// `EXPIRE` and `PEXPIRE` set a timeout on an existing key, replacing the
+/// one it had.
+#[tokio::test]
+async fn expire_sets_timeout_on_existing_key() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    request(&mut conn, &["SET", "foo", "bar"]).await;
+    assert_eq!(
+        Frame::Integer(1),
+        request(&mut conn, &["EXPIRE", "foo", "100"]).await
+    );
+    let remaining = |frame| match frame {
+        Frame::Integer(at) => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            at - now.as_millis() as i64
+        }
+        frame => panic!("expected integer, got {:?}", frame),
+    };
+    let ms = remaining(request(&mut conn, &["PEXPIRETIME", "foo"]).await);
+    assert!((99_000..=100_000).contains(&ms), "{}", ms);
+
+    assert_eq!(
+        Frame::Integer(1),
+        request(&mut conn, &["PEXPIRE", "foo", "5000"]).await
+    );
+    let ms = remaining(request(&mut conn, &["PEXPIRETIME", "foo"]).await);
+    assert!((4_000..=5_000).contains(&ms), "{}", ms);
+    assert_eq!(
+        Frame::Bulk(Bytes::from("bar")),
+        request(&mut conn, &["GET", "foo"]).await
+    );
+
+    assert_eq!(
+        Frame::Integer(0),
+        request(&mut conn, &["EXPIRE", "missing", "100"]).await
+    );
+
+    // A timeout in the past expires the key right away.
+    assert_eq!(
+        Frame::Integer(1),
+        request(&mut conn, &["EXPIRE", "foo", "-1"]).await
+    );
+    assert_eq!(Frame::Null, request(&mut conn, &["GET", "foo"]).await);
+}
+
+/// `PERSIST` cancels a timeout, so the key outlives it.
+#[tokio::test]
+async fn persist_keeps_key_past_original_timeout() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    request(&mut conn, &["SET", "foo", "bar", "PX", "50"]).await;
+    request(&mut conn, &["SET", "persistent", "bar"]).await;
+
+    assert_eq!(
+        Frame::Integer(1),
+        request(&mut conn, &["PERSIST", "foo"]).await
+    );
+    assert_eq!(
+        Frame::Integer(0),
+        request(&mut conn, &["PERSIST", "foo"]).await
+    );
+    assert_eq!(
+        Frame::Integer(0),
+        request(&mut conn, &["PERSIST", "persistent"]).await
+    );
+    assert_eq!(
+        Frame::Integer(0),
+        request(&mut conn, &["PERSIST", "missing"]).await
+    );
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        Frame::Bulk(Bytes::from("bar")),
+        request(&mut conn, &["GET", "foo"]).await
+    );
+    assert_eq!(
+        Frame::Integer(-1),
+        request(&mut conn, &["PEXPIRETIME", "foo"]).await
+    );
+}
+
+/// `SET` stores conditionally with `NX` and `XX`, and replies with the prior
+/// value with `GET`.
+#[tokio::test]
+async fn set_nx_xx_and_get() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    let ok = Frame::Simple("OK".to_string());
+    let bulk = |s: &'static str| Frame::Bulk(Bytes::from(s));
+
+    assert_eq!(
+        Frame::Null,
+        request(&mut conn, &["SET", "foo", "1", "XX"]).await
+    );
+    assert_eq!(ok, request(&mut conn, &["SET", "foo", "1", "NX"]).await);
+    assert_eq!(
+        Frame::Null,
+        request(&mut conn, &["SET", "foo", "2", "nx"]).await
+    );
+    assert_eq!(bulk("1"), request(&mut conn, &["GET", "foo"]).await);
+
+    assert_eq!(
+        ok,
+        request(&mut conn, &["SET", "foo", "2", "XX", "PX", "100000"]).await
+    );
+    assert_eq!(
+        bulk("2"),
+        request(&mut conn, &["SET", "foo", "3", "GET"]).await
+    );
+    assert_eq!(
+        Frame::Null,
+        request(&mut conn, &["SET", "bar", "1", "GET"]).await
+    );
+
+    // With NX, GET replies with the value that prevented storing.
+    assert_eq!(
+        bulk("3"),
+        request(&mut conn, &["SET", "foo", "4", "NX", "GET"]).await
+    );
+    assert_eq!(
+        Frame::Null,
+        request(&mut conn, &["SET", "baz", "1", "GET", "NX"]).await
+    );
+    assert_eq!(bulk("3"), request(&mut conn, &["GET", "foo"]).await);
+    assert_eq!(bulk("1"), request(&mut conn, &["GET", "baz"]).await);
+
+    request(&mut conn, &["XADD", "events", "*", "a", "1"]).await;
+    match request(&mut conn, &["SET", "events", "1", "GET"]).await {
+        Frame::Error(msg) => assert!(msg.starts_with("WRONGTYPE"), "{}", msg),
+        frame => panic!("expected error, got {:?}", frame),
+    }
+
+    // Contradictory or unknown options are replied to with an error, and the
+    // connection stays open.
+    let syntax_error = Frame::Error("ERR syntax error".to_string());
+    assert_eq!(
+        syntax_error,
+        request(&mut conn, &["SET", "foo", "5", "NX", "XX"]).await
+    );
+    assert_eq!(
+        syntax_error,
+        request(&mut conn, &["SET", "foo", "5", "KEEPALL"]).await
+    );
+    assert_eq!(
+        Frame::Error("ERR invalid expire time in 'set' command".to_string()),
+        request(&mut conn, &["SET", "foo", "5", "EX", "-1"]).await
+    );
+    assert_eq!(bulk("3"), request(&mut conn, &["GET", "foo"]).await);
+}
+
+/// An expiration too far to be represented is an error, which leaves the
+/// server serving.
+#[tokio::test]
+async fn set_with_overflowing_expiration() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    for (option, value) in [("EX", i64::MAX), ("PX", i64::MAX), ("EX", i64::MAX / 1000)] {
+        assert_eq!(
+            Frame::Error("ERR invalid expire time in 'set' command".to_string()),
+            request(
+                &mut conn,
+                &["SET", "foo", "bar", option, &value.to_string()]
+            )
+            .await
+        );
+    }
+    assert_eq!(Frame::Null, request(&mut conn, &["GET", "foo"]).await);
+
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    assert_eq!(
+        Frame::Simple("OK".to_string()),
+        request(&mut conn, &["SET", "foo", "bar"]).await
+    );
+}
+
+/// `SETNX` only stores a value at a missing key.
+#[tokio::test]
+async fn setnx_keeps_existing_value() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    assert_eq!(
+        Frame::Integer(1),
+        request(&mut conn, &["SETNX", "foo", "first"]).await
+    );
+    assert_eq!(
+        Frame::Integer(0),
+        request(&mut conn, &["SETNX", "foo", "second"]).await
+    );
+    assert_eq!(
+        Frame::Bulk(Bytes::from("first")),
+        request(&mut conn, &["GET", "foo"]).await
+    );
+    assert_eq!(
+        Frame::Integer(-1),
+        request(&mut conn, &["PEXPIRETIME", "foo"]).await
+    );
+}
+
+/// `MGET` replies with one element per key, in request order.
+#[tokio::test]
+async fn mget_preserves_order() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    request(&mut conn, &["SET", "a", "1"]).await;
+    request(&mut conn, &["SET", "b", "2"]).await;
+    request(&mut conn, &["SET", "expired", "3", "PX", "10"]).await;
+    request(&mut conn, &["XADD", "events", "*", "a", "1"]).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("2")),
+            Frame::Null,
+            Frame::Bulk(Bytes::from("1")),
+            Frame::Null,
+            Frame::Null,
+            Frame::Bulk(Bytes::from("2")),
+        ]),
+        request(
+            &mut conn,
+            &["MGET", "b", "missing", "a", "expired", "events", "b"]
+        )
+        .await
+    );
+
+    // Without keys, the error is replied and the connection stays open.
+    assert_eq!(
+        Frame::Error("ERR wrong number of arguments for 'mget' command".to_string()),
+        request(&mut conn, &["MGET"]).await
+    );
+    assert_eq!(
+        Frame::Array(vec![Frame::Bulk(Bytes::from("1"))]),
+        request(&mut conn, &["MGET", "a"]).await
+    );
+}
+
+/// `MSET` stores every pair, and replies with an error to a key without a
+/// value.
+#[tokio::test]
+async fn mset_stores_all_pairs() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    request(&mut conn, &["SET", "b", "old", "PX", "100000"]).await;
+    assert_eq!(
+        Frame::Simple("OK".to_string()),
+        request(&mut conn, &["MSET", "a", "1", "b", "2", "c", "3"]).await
+    );
+    assert_eq!(
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from("1")),
+            Frame::Bulk(Bytes::from("2")),
+            Frame::Bulk(Bytes::from("3")),
+        ]),
+        request(&mut conn, &["MGET", "a", "b", "c"]).await
+    );
+    assert_eq!(
+        Frame::Integer(-1),
+        request(&mut conn, &["PEXPIRETIME", "b"]).await
+    );
+
+    // No arguments or an odd number of them is replied to with an error,
+    // without storing anything, and the connection stays open.
+    let arity_error = Frame::Error("ERR wrong number of arguments for 'mset' command".to_string());
+    assert_eq!(arity_error, request(&mut conn, &["MSET"]).await);
+    assert_eq!(
+        arity_error,
+        request(&mut conn, &["MSET", "d", "4", "e"]).await
+    );
+    assert_eq!(
+        Frame::Integer(0),
+        request(&mut conn, &["EXISTS", "d"]).await
+    );
+}
+
+/// `APPEND` creates a missing key or extends the string it holds, and
+/// replies with the new length.
+#[tokio::test]
+async fn append_to_missing_and_existing() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    assert_eq!(
+        Frame::Integer(5),
+        request(&mut conn, &["APPEND", "foo", "Hello"]).await
+    );
+    assert_eq!(
+        Frame::Integer(11),
+        request(&mut conn, &["APPEND", "foo", " World"]).await
+    );
+    assert_eq!(
+        Frame::Bulk(Bytes::from("Hello World")),
+        request(&mut conn, &["GET", "foo"]).await
+    );
+
+    request(&mut conn, &["XADD", "events", "*", "a", "1"]).await;
+    match request(&mut conn, &["APPEND", "events", "x"]).await {
+        Frame::Error(msg) => assert!(msg.starts_with("WRONGTYPE"), "{}", msg),
+        frame => panic!("expected error, got {:?}", frame),
+    }
+}
+
+/// `GETDEL` returns the value once, removing the key with its expiration.
+#[tokio::test]
+async fn getdel_returns_value_once() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    request(&mut conn, &["SET", "foo", "bar", "PX", "100000"]).await;
+    assert_eq!(
+        Frame::Bulk(Bytes::from("bar")),
+        request(&mut conn, &["GETDEL", "foo"]).await
+    );
+    assert_eq!(Frame::Null, request(&mut conn, &["GETDEL", "foo"]).await);
+    assert_eq!(Frame::Null, request(&mut conn, &["GET", "foo"]).await);
+    assert_eq!(
+        Frame::Array(vec![]),
+        request(&mut conn, &["DEBUG", "EXPIRATIONS"]).await
+    );
+}
+
+/// `HELLO 3` replies with a map and switches the connection to RESP3, until
+/// `HELLO 2` switches it back.
+#[tokio::test]
+async fn hello_negotiates_protocol() {
+    let server = TestServer::start().await.unwrap();
+    let mut conn = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    let proto = |frame: Frame| {
+        let pairs = match frame {
+            Frame::Map(pairs) => pairs,
+            frame => panic!("expected map, got {:?}", frame),
+        };
+        pairs
+            .into_iter()
+            .find(|(key, _)| *key == Frame::Bulk(Bytes::from("proto")))
+            .map(|(_, value)| value)
+    };
+
+    // The client expects RESP3 replies, starting with that of `HELLO 3`.
+    conn.set_protocol(3);
+    let reply = request(&mut conn, &["HELLO", "3"]).await;
+    assert_eq!(Some(Frame::Integer(3)), proto(reply));
+
+    // The connection stays in RESP3 mode.
+    let reply = request(&mut conn, &["HELLO"]).await;
+    assert_eq!(Some(Frame::Integer(3)), proto(reply));
+    assert_eq!(Frame::Null, request(&mut conn, &["GET", "missing"]).await);
+
+    match request(&mut conn, &["HELLO", "2"]).await {
+        Frame::Array(fields) => assert_eq!(12, fields.len()),
+        frame => panic!("expected array, got {:?}", frame),
+    }
+
+    match request(&mut conn, &["HELLO", "4"]).await {
+        Frame::Error(msg) => assert!(msg.starts_with("NOPROTO"), "{}", msg),
+        frame => panic!("expected error, got {:?}", frame),
+    }
+}
+
+/// After `HELLO 3`, pub/sub replies and messages are pushes.
+#[tokio::test]
+async fn resp3_subscriber_receives_pushes() {
+    let server = TestServer::start().await.unwrap();
+    let mut subscriber = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+    let mut publisher = Connection::new(TcpStream::connect(server.addr()).await.unwrap());
+
+    subscriber.set_protocol(3);
+    request(&mut subscriber, &["HELLO", "3"]).await;
+    let push = |frame| match frame {
+        Frame::Array(items) => Frame::Push(items),
+        frame => panic!("expected array, got {:?}", frame),
+    };
+
+    assert_eq!(
+        push(frame!["subscribe", "hello", 1]),
+        request(&mut subscriber, &["SUBSCRIBE", "hello"]).await
+    );
+    request(&mut publisher, &["PUBLISH", "hello", "world"]).await;
+    assert_eq!(
+        Some(push(frame!["message", "hello", "world"])),
+        subscriber.read_frame().await.unwrap()
+    );
+}
+
+/// Commands typed in a terminal, without RESP framing, are served.
+#[tokio::test]
+async fn inline_commands() {
+    let server = TestServer::start().await.unwrap();
+    let mut stream = TcpStream::connect(server.addr()).await.unwrap();
+
+    stream
+        .write_all(b"SET foo \"hello world\"\r\n\nget foo\n")
+        .await
+        .unwrap();
+
+    let mut response = [0; 23];
+    stream.read_exact(&mut response).await.unwrap();
+    assert_eq!(b"+OK\r\n$11\r\nhello world\r\n", &response);
+}
+
+/// Independent servers bind distinct ports and serve their own keyspace.
+#[tokio::test]
+async fn test_servers_are_independent() {
+    let (first, second) = tokio::join!(TestServer::start(), TestServer::start());
+    let (first, second) = (first.unwrap(), second.unwrap());
+    assert_ne!(first.addr(), second.addr());
+
+    let mut conn = Connection::new(TcpStream::connect(first.addr()).await.unwrap());
+    assert_eq!(
+        Frame::Simple("OK".to_string()),
+        request(&mut conn, &["SET", "foo", "bar"]).await
+    );
+
+    let mut conn = Connection::new(TcpStream::connect(second.addr()).await.unwrap());
+    assert_eq!(Frame::Null, request(&mut conn, &["GET", "foo"]).await);
+
+    first.shutdown().await;
+    second.shutdown().await;
+}
+
+/// Send a command built from `args` and wait for the response.
+async fn request(conn: &mut Connection, args: &[&str]) -> Frame {
+    let frame = Frame::Array(
+        args.iter()
+            .map(|arg| Frame::Bulk(Bytes::copy_from_slice(arg.as_bytes())))
+            .collect(),
+    );
+    conn.write_frame(&frame).await.unwrap();
+    conn.read_frame().await.unwrap().unwrap()
+}